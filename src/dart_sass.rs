@@ -0,0 +1,136 @@
+use crate::download::{
+    cache_key, fetch_release_digest, fetch_with_mirrors, lock_dir, Checksum, Extract, ReleaseConfig,
+};
+use serde::Deserialize;
+use std::{
+    env::consts::{ARCH, OS},
+    path::{Path, PathBuf},
+};
+use tokio::process::Command;
+
+const DEFAULT_RELEASES_URL: &str = "https://api.github.com/repos/sass/dart-sass/releases";
+const DEFAULT_MIRROR: &str = "https://github.com/sass/dart-sass/releases/download/{version}";
+const ARCHIVE_DIR: &str = "dart-sass";
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct DartSassConfig {
+    release: ReleaseConfig,
+}
+
+impl DartSassConfig {
+    fn version(&self) -> &str {
+        &self.release.version
+    }
+
+    fn asset_suffix(&self) -> Result<String, anyhow::Error> {
+        self.release.asset_suffix(platform_asset_suffix)
+    }
+
+    fn install_dir(&self) -> Result<PathBuf, anyhow::Error> {
+        Ok(self
+            .release
+            .cache_dir("dart-sass")?
+            .join(cache_key(self.version(), &self.asset_suffix()?)))
+    }
+}
+
+fn platform_asset_suffix() -> Result<String, anyhow::Error> {
+    let unsupported = || {
+        anyhow::anyhow!(
+            "当前平台（{}-{}）没有可用的Dart Sass发布包！请在[dart_sass]中通过asset_suffix指定",
+            OS,
+            ARCH
+        )
+    };
+
+    let os = match OS {
+        "linux" => "linux",
+        "macos" => "macos",
+        "windows" => "windows",
+        _ => return Err(unsupported()),
+    };
+    let arch = match ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        "x86" => "ia32",
+        _ => return Err(unsupported()),
+    };
+    let libc = if cfg!(target_env = "musl") {
+        "-musl"
+    } else {
+        ""
+    };
+    let ext = if os == "windows" { "zip" } else { "tar.gz" };
+
+    Ok(format!("{}-{}{}.{}", os, arch, libc, ext))
+}
+
+fn sass_path(dir: &Path) -> PathBuf {
+    let name = if cfg!(windows) { "sass.bat" } else { "sass" };
+    dir.join(name)
+}
+
+async fn check_sass(config: &DartSassConfig, dir: &Path) -> Result<bool, anyhow::Error> {
+    tracing::info!("正在校验现有Dart Sass版本……");
+
+    let Ok(output) = Command::new(sass_path(dir)).arg("--version").output().await else {
+        tracing::info!("Dart Sass不存在");
+        return Ok(false);
+    };
+    if !output.status.success() {
+        tracing::info!("sass --version执行失败，将重新安装");
+        return Ok(false);
+    }
+
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if version == config.version() {
+        tracing::info!("现有Dart Sass版本匹配！");
+        Ok(true)
+    } else {
+        tracing::info!("现有Dart Sass版本为{}，与要求不匹配", version);
+        Ok(false)
+    }
+}
+
+pub async fn fetch_dart_sass(config: &DartSassConfig) -> Result<PathBuf, anyhow::Error> {
+    tracing::info!("请求的Dart Sass版本是：{}", config.version());
+    let dir = config.install_dir()?;
+    let sass = dir.join(ARCHIVE_DIR);
+    tracing::info!("Dart Sass缓存路径：{}", sass.display());
+    let _lock = lock_dir(&dir).await?;
+
+    if check_sass(config, &sass).await? {
+        tracing::info!("将跳过下载");
+        return Ok(sass);
+    }
+
+    tracing::info!("准备下载Dart Sass");
+    let release = &config.release;
+    let asset = format!("dart-sass-{}-{}", config.version(), config.asset_suffix()?);
+    let client = release.client()?;
+    let expected = match release.sha256() {
+        Some(sha256) => sha256,
+        None => {
+            let url = format!(
+                "{}/tags/{}",
+                release.releases_url(DEFAULT_RELEASES_URL),
+                config.version()
+            );
+            fetch_release_digest(&client, &url, &asset)
+                .await
+                .map_err(|err| anyhow::anyhow!("{}请在[dart_sass]中通过sha256指定", err))?
+        }
+    };
+
+    fetch_with_mirrors(
+        &client,
+        &release.mirrors(DEFAULT_MIRROR, config.version()),
+        &asset,
+        &Checksum::Sha256(expected),
+        &dir,
+        Extract::Dir(ARCHIVE_DIR),
+    )
+    .await
+}
@@ -0,0 +1,89 @@
+use crate::{
+    hugo::{fetch_hugo, HugoConfig},
+    release::{fetch_release, ReleaseConfig, ReleaseTool},
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum GeneratorConfig {
+    Hugo(HugoConfig),
+    Zola(ReleaseConfig),
+    Mdbook(ReleaseConfig),
+}
+
+impl GeneratorConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Hugo(_) => "hugo",
+            Self::Zola(_) => ReleaseTool::Zola.name(),
+            Self::Mdbook(_) => ReleaseTool::Mdbook.name(),
+        }
+    }
+
+    pub async fn resolve_version(
+        &mut self,
+        update_lock: bool,
+        dry_run: bool,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Hugo(config) => config.resolve_version(update_lock, dry_run).await,
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn fetch(&self) -> Result<PathBuf, anyhow::Error> {
+        match self {
+            Self::Hugo(config) => fetch_hugo(config).await,
+            Self::Zola(config) => fetch_release(ReleaseTool::Zola, config).await,
+            Self::Mdbook(config) => fetch_release(ReleaseTool::Mdbook, config).await,
+        }
+    }
+
+    pub fn build_command(
+        &self,
+        binary: &Path,
+        base_url: Option<&str>,
+        drafts: bool,
+        destination: &Path,
+    ) -> Command {
+        let mut command = Command::new(binary);
+
+        match self {
+            Self::Hugo(_) => {
+                if let Some(base_url) = base_url {
+                    command.arg("-b").arg(base_url);
+                }
+                // Same as the old `-D -F` draft build: drafts plus future-dated content.
+                if drafts {
+                    command.arg("--buildDrafts").arg("--buildFuture");
+                }
+                command.arg("--destination").arg(destination);
+            }
+            Self::Zola(_) => {
+                command.arg("build");
+                if let Some(base_url) = base_url {
+                    command.arg("--base-url").arg(base_url);
+                }
+                if drafts {
+                    command.arg("--drafts");
+                }
+                command.arg("--output-dir").arg(destination);
+            }
+            Self::Mdbook(_) => {
+                command.arg("build");
+                if base_url.is_some() {
+                    tracing::warn!("mdbook不支持base_url，已忽略");
+                }
+                if drafts {
+                    tracing::warn!("mdbook不支持drafts，已忽略");
+                }
+                command.arg("--dest-dir").arg(destination);
+            }
+        }
+
+        command
+    }
+}
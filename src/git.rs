@@ -0,0 +1,390 @@
+use git2::{
+    build::CheckoutBuilder, BranchType, Cred, Direction, FetchOptions, IndexAddOption, PushOptions,
+    RemoteCallbacks, Repository, Signature,
+};
+use std::{cell::Cell, error, fmt, path::Path};
+
+#[derive(Debug)]
+pub struct GitError {
+    pub step: &'static str,
+    pub source: git2::Error,
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "git {}失败：{}（class：{:?}，code：{:?}）",
+            self.step,
+            self.source.message(),
+            self.source.class(),
+            self.source.code()
+        )
+    }
+}
+
+impl error::Error for GitError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+trait GitStep<T> {
+    fn step(self, step: &'static str) -> Result<T, GitError>;
+}
+
+impl<T> GitStep<T> for Result<T, git2::Error> {
+    fn step(self, step: &'static str) -> Result<T, GitError> {
+        self.map_err(|source| GitError { step, source })
+    }
+}
+
+#[derive(Clone)]
+pub struct GitCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl GitCredentials {
+    fn callbacks(&self) -> RemoteCallbacks<'_> {
+        let attempted = Cell::new(false);
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |_, _, _| {
+            if attempted.replace(true) {
+                return Err(git2::Error::from_str("认证失败！"));
+            }
+            Cred::userpass_plaintext(&self.username, &self.password)
+        });
+        callbacks
+    }
+}
+
+fn remote_branch(
+    repo: &Repository,
+    url: &str,
+    branch: Option<&str>,
+    credentials: &GitCredentials,
+) -> Result<(String, bool), GitError> {
+    let mut remote = repo.remote_anonymous(url).step("ls-remote")?;
+    let connection = remote
+        .connect_auth(Direction::Fetch, Some(credentials.callbacks()), None)
+        .step("ls-remote")?;
+
+    let branch = match branch {
+        Some(branch) => branch.to_owned(),
+        None => {
+            let head = connection.default_branch().step("ls-remote")?;
+            let head = head.as_str().step("ls-remote")?;
+            head.trim_start_matches("refs/heads/").to_owned()
+        }
+    };
+    let refname = format!("refs/heads/{}", branch);
+    let exists = connection
+        .list()
+        .step("ls-remote")?
+        .iter()
+        .any(|head| head.name() == refname);
+
+    Ok((branch, exists))
+}
+
+fn checkout(repo: &Repository, branch: &str) -> Result<(), GitError> {
+    let upstream = format!("origin/{}", branch);
+    let commit = repo
+        .find_branch(&upstream, BranchType::Remote)
+        .and_then(|remote| remote.get().peel_to_commit())
+        .step("checkout")?;
+    repo.branch(branch, &commit, false)
+        .and_then(|mut local| local.set_upstream(Some(&upstream)))
+        .step("checkout")?;
+
+    repo.set_head(&format!("refs/heads/{}", branch))
+        .step("checkout")?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))
+        .step("checkout")
+}
+
+pub fn clone(
+    url: &str,
+    path: &Path,
+    branch: Option<&str>,
+    depth: u32,
+    credentials: &GitCredentials,
+) -> Result<Repository, GitError> {
+    let repo = Repository::init(path).step("init")?;
+    let (branch, exists) = remote_branch(&repo, url, branch, credentials)?;
+    let refname = format!("refs/heads/{}", branch);
+
+    if !exists {
+        tracing::info!("远程仓库中没有{}分支，将新建", branch);
+        repo.set_head(&refname).step("checkout")?;
+        repo.remote("origin", url).step("remote")?;
+        return Ok(repo);
+    }
+
+    let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch);
+    let mut fetch = FetchOptions::new();
+    fetch.remote_callbacks(credentials.callbacks());
+    if depth > 0 {
+        fetch.depth(depth as i32);
+    }
+    repo.remote_with_fetch("origin", url, &refspec)
+        .and_then(|mut remote| remote.fetch(&[&refspec], Some(&mut fetch), None))
+        .step("fetch")?;
+
+    checkout(&repo, &branch)?;
+    Ok(repo)
+}
+
+pub fn commit_all(
+    repo: &Repository,
+    user_name: &str,
+    user_email: &str,
+    message: &str,
+) -> Result<bool, GitError> {
+    let mut index = repo.index().step("add")?;
+    index
+        .add_all(["*"], IndexAddOption::DEFAULT, None)
+        .step("add")?;
+    index.update_all(["*"], None).step("add")?;
+    index.write().step("add")?;
+    let tree_id = index.write_tree().step("add")?;
+
+    let parent = repo.head().and_then(|head| head.peel_to_commit()).ok();
+    if parent
+        .as_ref()
+        .is_some_and(|parent| parent.tree_id() == tree_id)
+    {
+        return Ok(false);
+    }
+
+    let tree = repo.find_tree(tree_id).step("commit")?;
+    let signature = Signature::now(user_name, user_email).step("commit")?;
+    let parents = parent.iter().collect::<Vec<_>>();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .step("commit")?;
+    Ok(true)
+}
+
+pub fn push(repo: &Repository, credentials: &GitCredentials) -> Result<(), GitError> {
+    let head = repo.head().step("push")?;
+    let refname = head.name().step("push")?;
+    let rejected = Cell::new(None);
+
+    {
+        let mut callbacks = credentials.callbacks();
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(status) = status {
+                rejected.set(Some(format!("{}被拒绝：{}", refname, status)));
+            }
+            Ok(())
+        });
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+
+        repo.find_remote("origin")
+            .and_then(|mut remote| remote.push(&[format!("{0}:{0}", refname)], Some(&mut options)))
+            .step("push")?;
+    }
+
+    match rejected.take() {
+        Some(message) => Err(git2::Error::from_str(&message)).step("push"),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        net::{TcpListener, TcpStream},
+        path::PathBuf,
+        process::{Child, Command, Stdio},
+        thread,
+        time::Duration,
+    };
+    use tempfile::TempDir;
+
+    fn credentials() -> GitCredentials {
+        GitCredentials {
+            username: "user".to_owned(),
+            password: "token".to_owned(),
+        }
+    }
+
+    /// libgit2's local transport rejects depth, so file:// clones below always use depth 0.
+    fn bare_repo(dir: &TempDir) -> (PathBuf, String) {
+        let path = dir.path().join("site.git");
+        Repository::init_bare(&path).unwrap();
+        let url = format!("file://{}", path.display());
+        (path, url)
+    }
+
+    fn deploy(
+        url: &str,
+        path: &Path,
+        branch: &str,
+        depth: u32,
+        file: &str,
+    ) -> Result<(), GitError> {
+        let repo = clone(url, path, Some(branch), depth, &credentials())?;
+        fs::write(path.join(file), file).unwrap();
+        assert!(commit_all(&repo, "user", "user@example.com", "Deploy")?);
+        push(&repo, &credentials())
+    }
+
+    fn branch_files(bare: &Path, branch: &str) -> Vec<String> {
+        let repo = Repository::open_bare(bare).unwrap();
+        let tree = repo
+            .find_reference(&format!("refs/heads/{}", branch))
+            .and_then(|reference| reference.peel_to_tree())
+            .unwrap();
+        tree.iter()
+            .map(|entry| entry.name().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn creates_missing_branch_as_orphan() {
+        let dir = tempfile::tempdir().unwrap();
+        let (bare, url) = bare_repo(&dir);
+
+        deploy(&url, &dir.path().join("a"), "main", 0, "index.html").unwrap();
+        deploy(&url, &dir.path().join("b"), "draft", 0, "draft.html").unwrap();
+
+        assert_eq!(branch_files(&bare, "main"), ["index.html"]);
+        assert_eq!(branch_files(&bare, "draft"), ["draft.html"]);
+        let repo = Repository::open_bare(&bare).unwrap();
+        let draft = repo
+            .find_reference("refs/heads/draft")
+            .and_then(|reference| reference.peel_to_commit())
+            .unwrap();
+        assert_eq!(draft.parent_count(), 0);
+    }
+
+    #[test]
+    fn skips_commit_without_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, url) = bare_repo(&dir);
+        deploy(&url, &dir.path().join("a"), "main", 0, "index.html").unwrap();
+
+        let path = dir.path().join("b");
+        let repo = clone(&url, &path, Some("main"), 0, &credentials()).unwrap();
+        assert!(path.join("index.html").is_file());
+        assert!(!commit_all(&repo, "user", "user@example.com", "Deploy").unwrap());
+    }
+
+    #[test]
+    fn reports_rejected_push() {
+        let dir = tempfile::tempdir().unwrap();
+        let (bare, url) = bare_repo(&dir);
+        deploy(&url, &dir.path().join("seed"), "main", 0, "index.html").unwrap();
+
+        let stale_path = dir.path().join("stale");
+        let stale = clone(&url, &stale_path, Some("main"), 0, &credentials()).unwrap();
+        deploy(&url, &dir.path().join("fresh"), "main", 0, "fresh.html").unwrap();
+
+        fs::write(stale_path.join("stale.html"), "stale").unwrap();
+        assert!(commit_all(&stale, "user", "user@example.com", "Deploy").unwrap());
+        let err = push(&stale, &credentials()).unwrap_err();
+        assert_eq!(err.step, "push");
+        assert_eq!(err.source.code(), git2::ErrorCode::NotFastForward);
+        assert_eq!(branch_files(&bare, "main"), ["fresh.html", "index.html"]);
+    }
+
+    struct GitDaemon(Child);
+
+    impl Drop for GitDaemon {
+        fn drop(&mut self) {
+            self.0.kill().ok();
+            self.0.wait().ok();
+        }
+    }
+
+    /// Serves `base` over git://, since shallow clones need a smart protocol.
+    fn git_daemon(base: &Path) -> Option<(GitDaemon, u16)> {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .ok()?
+            .port();
+        // Run git-daemon itself: `git daemon` forks it, and killing the wrapper would leak it.
+        let exec_path = Command::new("git").arg("--exec-path").output().ok()?;
+        let exec_path = String::from_utf8(exec_path.stdout).ok()?;
+        let child = Command::new(Path::new(exec_path.trim()).join("git-daemon"))
+            .arg("--export-all")
+            .arg("--enable=receive-pack")
+            .arg("--listen=127.0.0.1")
+            .arg(format!("--port={}", port))
+            .arg(format!("--base-path={}", base.display()))
+            .arg(base)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let daemon = GitDaemon(child);
+
+        for _ in 0..50 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return Some((daemon, port));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        None
+    }
+
+    #[test]
+    fn pushes_from_shallow_clone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (bare, url) = bare_repo(&dir);
+        for file in ["1.html", "2.html", "3.html"] {
+            deploy(&url, &dir.path().join(file), "main", 0, file).unwrap();
+        }
+
+        let Some((_daemon, port)) = git_daemon(dir.path()) else {
+            eprintln!("git daemon不可用，跳过浅克隆测试");
+            return;
+        };
+        let url = format!("git://127.0.0.1:{}/site.git", port);
+        let path = dir.path().join("shallow");
+        deploy(&url, &path, "main", 1, "4.html").unwrap();
+
+        let repo = Repository::open(&path).unwrap();
+        assert!(repo.is_shallow());
+        assert_eq!(
+            branch_files(&bare, "main"),
+            ["1.html", "2.html", "3.html", "4.html"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_push_declined_by_remote() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (bare, url) = bare_repo(&dir);
+        deploy(&url, &dir.path().join("seed"), "main", 0, "index.html").unwrap();
+
+        let hook = bare.join("hooks/pre-receive");
+        fs::write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let Some((_daemon, port)) = git_daemon(dir.path()) else {
+            eprintln!("git daemon不可用，跳过远程拒绝测试");
+            return;
+        };
+        let url = format!("git://127.0.0.1:{}/site.git", port);
+        let err = deploy(&url, &dir.path().join("declined"), "main", 1, "new.html").unwrap_err();
+        assert_eq!(err.step, "push");
+        assert!(err.to_string().contains("refs/heads/main被拒绝"), "{}", err);
+        assert_eq!(branch_files(&bare, "main"), ["index.html"]);
+    }
+}
@@ -0,0 +1,774 @@
+use crate::{
+    download::{
+        cache_key, cache_root, fetch_with_mirrors, lock_dir, Checksum, Extract, ReleaseConfig,
+    },
+    hugo_version::hugo_version,
+    retain_decimal_places,
+};
+use fs_extra::dir;
+use reqwest::Client;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::{
+    env::{
+        self,
+        consts::{ARCH, EXE_SUFFIX, OS},
+    },
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::{self, remove_dir_all},
+    task::spawn_blocking,
+};
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum HugoEdition {
+    Standard,
+    #[default]
+    Extended,
+    ExtendedWithdeploy,
+}
+
+impl HugoEdition {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::Extended => "extended",
+            Self::ExtendedWithdeploy => "extended_withdeploy",
+        }
+    }
+
+    fn asset_prefix(self) -> &'static str {
+        match self {
+            Self::Standard => "hugo",
+            Self::Extended => "hugo_extended",
+            Self::ExtendedWithdeploy => "hugo_extended_withdeploy",
+        }
+    }
+
+    pub fn from_build_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Self {
+        let tags: Vec<&str> = tags.into_iter().collect();
+
+        if !tags.contains(&"extended") {
+            Self::Standard
+        } else if tags.contains(&"withdeploy") {
+            Self::ExtendedWithdeploy
+        } else {
+            Self::Extended
+        }
+    }
+}
+
+const DEFAULT_RELEASES_URL: &str = "https://api.github.com/repos/gohugoio/hugo/releases";
+const DEFAULT_MIRROR: &str = "https://github.com/gohugoio/hugo/releases/download/v{version}";
+const RELEASES_PER_PAGE: usize = 100;
+const LOCK_FILE: &str = "workflow.lock";
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    draft: bool,
+    prerelease: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct HugoLock {
+    requirement: String,
+    version: String,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct LockFile {
+    hugo: Option<HugoLock>,
+}
+
+impl LockFile {
+    async fn read() -> Result<Self, anyhow::Error> {
+        if Path::new(LOCK_FILE).is_file() {
+            Ok(toml::from_str(&fs::read_to_string(LOCK_FILE).await?)?)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    async fn write(&self) -> Result<(), anyhow::Error> {
+        tracing::info!("正在写入{}……", LOCK_FILE);
+        Ok(fs::write(LOCK_FILE, toml::to_string(self)?).await?)
+    }
+}
+
+async fn resolve_release(
+    client: &Client,
+    releases_url: &str,
+    req: Option<&VersionReq>,
+) -> Result<Version, anyhow::Error> {
+    let mut best: Option<Version> = None;
+
+    for page in 1.. {
+        tracing::info!("正在GET：{}（第{}页）", releases_url, page);
+        let releases: Vec<Release> = client
+            .get(releases_url)
+            .query(&[("per_page", RELEASES_PER_PAGE), ("page", page)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let len = releases.len();
+
+        for release in releases {
+            if release.draft || release.prerelease {
+                continue;
+            }
+            let Ok(version) = Version::parse(release.tag_name.trim_start_matches('v')) else {
+                continue;
+            };
+            if req.is_none_or(|req| req.matches(&version))
+                && best.as_ref().is_none_or(|best| version > *best)
+            {
+                best = Some(version);
+            }
+        }
+
+        // GitHub lists releases newest first, so nothing on a later page can beat a match here.
+        if best.is_some() || len < RELEASES_PER_PAGE {
+            break;
+        }
+    }
+
+    best.ok_or(anyhow::anyhow!("没有满足要求的hugo版本！"))
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HugoSource {
+    #[default]
+    Download,
+    Path,
+    System,
+}
+
+#[derive(Deserialize)]
+pub struct HugoConfig {
+    #[serde(flatten)]
+    release: ReleaseConfig,
+    #[serde(default)]
+    edition: HugoEdition,
+    #[serde(default)]
+    source: HugoSource,
+    path: Option<PathBuf>,
+    #[serde(default)]
+    download_fallback: bool,
+}
+
+impl HugoConfig {
+    fn version(&self) -> &str {
+        &self.release.version
+    }
+
+    fn cached_path(&self) -> Result<PathBuf, anyhow::Error> {
+        Ok(self
+            .cache_dir()?
+            .join(self.cache_key()?)
+            .join(format!("hugo{}", EXE_SUFFIX)))
+    }
+
+    fn binary_path(&self) -> Result<PathBuf, anyhow::Error> {
+        match self.source {
+            HugoSource::Download => self.cached_path(),
+            HugoSource::Path => self
+                .path
+                .clone()
+                .ok_or(anyhow::anyhow!("hugo配置中source为path时必须指定path")),
+            HugoSource::System => find_in_path(&format!("hugo{}", EXE_SUFFIX))
+                .ok_or(anyhow::anyhow!("PATH中未找到hugo")),
+        }
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        Version::parse(self.version()).is_ok()
+    }
+
+    pub async fn read_locked_version(&mut self) -> Result<(), anyhow::Error> {
+        if self.is_resolved() {
+            return Ok(());
+        }
+
+        match LockFile::read().await?.hugo {
+            Some(locked) if locked.requirement == self.version() => {
+                tracing::info!("使用{}中锁定的hugo版本：{}", LOCK_FILE, locked.version);
+                self.release.version = locked.version;
+            }
+            _ => tracing::warn!(
+                "hugo版本要求{}尚未解析（{}中无记录）",
+                self.version(),
+                LOCK_FILE
+            ),
+        }
+
+        Ok(())
+    }
+
+    pub async fn resolve_version(
+        &mut self,
+        update_lock: bool,
+        dry_run: bool,
+    ) -> Result<(), anyhow::Error> {
+        let requirement = self.version().to_owned();
+        let req = if requirement == "latest" {
+            None
+        } else if Version::parse(&requirement).is_ok() {
+            return Ok(());
+        } else {
+            Some(VersionReq::parse(&requirement)?)
+        };
+
+        let mut lock = LockFile::read().await?;
+        if !update_lock {
+            if let Some(locked) = &lock.hugo {
+                if locked.requirement == requirement {
+                    tracing::info!("使用{}中锁定的hugo版本：{}", LOCK_FILE, locked.version);
+                    self.release.version = locked.version.clone();
+                    return Ok(());
+                }
+            }
+        }
+
+        tracing::info!("正在解析hugo版本要求：{}", requirement);
+        let releases_url = self.release.releases_url(DEFAULT_RELEASES_URL);
+        let version = resolve_release(&self.release.client()?, releases_url, req.as_ref())
+            .await?
+            .to_string();
+        tracing::info!("hugo版本要求{}解析为：{}", requirement, version);
+
+        lock.hugo = Some(HugoLock {
+            requirement,
+            version: version.clone(),
+        });
+        if dry_run {
+            tracing::info!("[dry-run] 将写入{}", LOCK_FILE);
+        } else {
+            lock.write().await?;
+        }
+
+        self.release.version = version;
+        Ok(())
+    }
+
+    fn cache_dir(&self) -> Result<PathBuf, anyhow::Error> {
+        self.release.cache_dir("hugo")
+    }
+
+    fn asset_suffix(&self) -> Result<String, anyhow::Error> {
+        self.release
+            .asset_suffix(|| platform_asset_suffix(self.edition, OS, ARCH))
+    }
+
+    fn cache_key(&self) -> Result<String, anyhow::Error> {
+        Ok(cache_key(
+            &format!("{}_{}", self.edition.name(), self.version()),
+            &self.asset_suffix()?,
+        ))
+    }
+}
+
+fn platform_asset_suffix(
+    edition: HugoEdition,
+    os: &str,
+    arch: &str,
+) -> Result<String, anyhow::Error> {
+    let unsupported = || {
+        anyhow::anyhow!(
+            "当前平台（{}-{}）没有可用的hugo发布包！请在hugo配置中通过asset_suffix指定",
+            os,
+            arch
+        )
+    };
+
+    let os = match os {
+        "linux" => "linux",
+        "macos" => return Ok("darwin-universal.tar.gz".into()),
+        "windows" => "windows",
+        "freebsd" => "freebsd",
+        "netbsd" => "netbsd",
+        "openbsd" => "openbsd",
+        _ => return Err(unsupported()),
+    };
+    let arch = match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        _ => return Err(unsupported()),
+    };
+
+    // Extended builds need cgo, so they are only published for these platforms.
+    let extended = matches!(
+        (os, arch),
+        ("linux", "amd64") | ("linux", "arm64") | ("windows", "amd64")
+    );
+    if edition != HugoEdition::Standard && !extended {
+        return Err(anyhow::anyhow!(
+            "hugo没有{}-{}平台的{}版发布包！请将edition改为standard，或在hugo配置中通过asset_suffix指定",
+            os,
+            arch,
+            edition.name()
+        ));
+    }
+
+    let ext = if os == "windows" { "zip" } else { "tar.gz" };
+    Ok(format!("{}-{}.{}", os, arch, ext))
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn default_cache_dir() -> Result<PathBuf, anyhow::Error> {
+    Ok(cache_root()?.join("hugo"))
+}
+
+fn cache_dir_of(config: Option<&HugoConfig>) -> Result<PathBuf, anyhow::Error> {
+    match config {
+        Some(config) => config.cache_dir(),
+        None => default_cache_dir(),
+    }
+}
+
+async fn cache_entries(cache_dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut entries = Vec::new();
+
+    if cache_dir.is_dir() {
+        let mut read_dir = fs::read_dir(cache_dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                entries.push(entry.path());
+            }
+        }
+    }
+
+    entries.sort();
+    Ok(entries)
+}
+
+pub async fn cache_list(config: Option<&HugoConfig>) -> Result<(), anyhow::Error> {
+    let cache_dir = cache_dir_of(config)?;
+    let current = config
+        .filter(|config| config.is_resolved())
+        .and_then(|config| config.cache_key().ok());
+    tracing::info!("hugo缓存目录：{}", cache_dir.display());
+
+    let entries = cache_entries(&cache_dir).await?;
+    if entries.is_empty() {
+        tracing::info!("缓存为空！");
+    }
+
+    for entry in entries {
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+        let size = spawn_blocking({
+            let entry = entry.clone();
+            move || dir::get_size(entry)
+        })
+        .await??;
+
+        tracing::info!(
+            "{}（{} MB）{}",
+            name,
+            retain_decimal_places(size as f64 / 1024.0 / 1024.0, 3),
+            if current.as_deref() == Some(&*name) {
+                "（当前）"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether `name` looks like a directory created by [`HugoConfig::cache_key`].
+fn is_cache_key(name: &str) -> bool {
+    let editions = [
+        HugoEdition::ExtendedWithdeploy,
+        HugoEdition::Extended,
+        HugoEdition::Standard,
+    ];
+    let Some(rest) = editions
+        .iter()
+        .find_map(|edition| name.strip_prefix(edition.name())?.strip_prefix('_'))
+    else {
+        return false;
+    };
+    let Some((version, platform)) = rest.split_once('_') else {
+        return false;
+    };
+
+    Version::parse(version).is_ok()
+        && !platform.is_empty()
+        && platform
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+pub async fn cache_prune(config: Option<&HugoConfig>, all: bool) -> Result<(), anyhow::Error> {
+    let cache_dir = cache_dir_of(config)?;
+    let keep = match config {
+        _ if all => None,
+        Some(config) if config.is_resolved() => Some(config.cache_key()?),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "无法确定需要保留的hugo版本！如需清理全部缓存，请使用--all"
+            ))
+        }
+    };
+    let mut pruned = 0;
+
+    for entry in cache_entries(&cache_dir).await? {
+        let name = entry.file_name().and_then(|name| name.to_str());
+        if !name.is_some_and(is_cache_key) {
+            tracing::warn!("跳过非hugo缓存目录：{}", entry.display());
+            continue;
+        }
+
+        if keep.as_deref() != name {
+            let _lock = lock_dir(&entry).await?;
+            tracing::info!("正在清理：{}", entry.display());
+            remove_dir_all(&entry).await?;
+            pruned += 1;
+        }
+    }
+
+    tracing::info!("已清理{}个缓存", pruned);
+    Ok(())
+}
+
+async fn check_hugo(config: &HugoConfig, hugo: &Path) -> Result<bool, anyhow::Error> {
+    tracing::info!("正在校验现有hugo版本……");
+
+    let version = match hugo_version(hugo).await {
+        Ok(version) => version,
+        Err(err) => {
+            tracing::warn!("无法获取现有hugo版本：{}", err);
+            return Ok(false);
+        }
+    };
+
+    if let Some((_, info)) = version {
+        if info.version != Version::parse(config.version())? {
+            tracing::info!("现有hugo版本为{}，与要求不匹配", info.version);
+        } else if info.edition != config.edition {
+            tracing::info!(
+                "现有hugo版本为{}，请求的是{}，与要求不匹配",
+                info.edition.name(),
+                config.edition.name()
+            );
+        } else {
+            tracing::info!("现有hugo版本匹配！");
+            return Ok(true);
+        }
+    } else {
+        tracing::info!("hugo不存在");
+    }
+
+    Ok(false)
+}
+
+pub async fn fetch_hugo(config: &HugoConfig) -> Result<PathBuf, anyhow::Error> {
+    tracing::info!(
+        "请求的hugo版本是：{}（{}）",
+        config.version(),
+        config.edition.name()
+    );
+
+    if config.source != HugoSource::Download {
+        let err = match config.binary_path() {
+            Ok(hugo) => {
+                tracing::info!("hugo路径：{}", hugo.display());
+                if check_hugo(config, &hugo).await? {
+                    return Ok(hugo);
+                }
+                anyhow::anyhow!("{}的版本不满足要求", hugo.display())
+            }
+            Err(err) => err,
+        };
+
+        if !config.download_fallback {
+            return Err(anyhow::anyhow!("{}，且未开启download_fallback！", err));
+        }
+        tracing::warn!("{}，将改为下载hugo", err);
+    }
+
+    download_hugo(config).await
+}
+
+async fn download_hugo(config: &HugoConfig) -> Result<PathBuf, anyhow::Error> {
+    let version = config.version();
+    let hugo = config.cached_path()?;
+    let dir = hugo.parent().unwrap_or(Path::new("."));
+    tracing::info!("hugo缓存路径：{}", hugo.display());
+    let _lock = lock_dir(dir).await?;
+
+    if check_hugo(config, &hugo).await? {
+        tracing::info!("将跳过下载");
+        return Ok(hugo);
+    }
+
+    tracing::info!("准备下载hugo");
+    let release = &config.release;
+    let asset = format!(
+        "{}_{}_{}",
+        config.edition.asset_prefix(),
+        version,
+        config.asset_suffix()?
+    );
+    let checksum = match release.sha256() {
+        Some(sha256) => Checksum::Sha256(sha256),
+        None => Checksum::File(format!("hugo_{}_checksums.txt", version)),
+    };
+
+    fetch_with_mirrors(
+        &release.client()?,
+        &release.mirrors(DEFAULT_MIRROR, version),
+        &asset,
+        &checksum,
+        dir,
+        Extract::Binary("hugo"),
+    )
+    .await?;
+    Ok(hugo)
+}
+
+pub async fn hugo_info(config: &HugoConfig) -> Result<(), anyhow::Error> {
+    let hugo = config.binary_path()?;
+    tracing::info!("hugo路径：{}", hugo.display());
+
+    let Some((output, info)) = hugo_version(&hugo).await? else {
+        return Err(anyhow::anyhow!("hugo不存在！请先执行upgrade-hugo"));
+    };
+
+    tracing::info!("hugo version输出：{}", output);
+    tracing::info!("版本：{}", info.version);
+    tracing::info!("版本类型：{}", info.edition.name());
+    tracing::info!("提交：{}", info.commit.as_deref().unwrap_or("未知"));
+    tracing::info!("平台：{}", info.platform.as_deref().unwrap_or("未知"));
+    tracing::info!("构建日期：{}", info.build_date.as_deref().unwrap_or("未知"));
+    tracing::info!("发行方：{}", info.vendor.as_deref().unwrap_or("未知"));
+
+    if !config.is_resolved() {
+        tracing::warn!(
+            "workflow.toml要求的版本{}尚未解析，请先执行upgrade-hugo",
+            config.version()
+        );
+    } else if info.version == Version::parse(config.version())? && info.edition == config.edition {
+        tracing::info!(
+            "与workflow.toml要求的版本一致：{}（{}）",
+            config.version(),
+            config.edition.name()
+        );
+    } else {
+        tracing::warn!(
+            "与workflow.toml要求的版本不一致：{}（{}）",
+            config.version(),
+            config.edition.name()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    fn release(tag: &str, draft: bool, prerelease: bool) -> String {
+        format!(
+            r#"{{"tag_name":"{}","draft":{},"prerelease":{}}}"#,
+            tag, draft, prerelease
+        )
+    }
+
+    fn page(releases: &[String]) -> String {
+        format!("[{}]", releases.join(","))
+    }
+
+    /// Serves `pages` as a paginated releases API and records which pages were requested.
+    async fn serve_releases(pages: Vec<String>) -> (String, Arc<Mutex<Vec<usize>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/releases", listener.local_addr().unwrap());
+        let requested = Arc::new(Mutex::new(Vec::new()));
+
+        tokio::spawn({
+            let requested = requested.clone();
+            async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                    }
+
+                    let request = String::from_utf8(request).unwrap();
+                    let page: usize = request
+                        .split_once("&page=")
+                        .and_then(|(_, rest)| rest.split(|c: char| !c.is_ascii_digit()).next())
+                        .and_then(|page| page.parse().ok())
+                        .unwrap();
+                    requested.lock().unwrap().push(page);
+
+                    let body = pages.get(page - 1).map_or("[]", String::as_str);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            }
+        });
+
+        (url, requested)
+    }
+
+    async fn resolve(
+        pages: Vec<String>,
+        req: Option<&str>,
+    ) -> (Result<Version, anyhow::Error>, Vec<usize>) {
+        let (url, requested) = serve_releases(pages).await;
+        let client = Client::builder().no_proxy().build().unwrap();
+        let req = req.map(|req| VersionReq::parse(req).unwrap());
+        let res = resolve_release(&client, &url, req.as_ref()).await;
+        let requested = requested.lock().unwrap().clone();
+        (res, requested)
+    }
+
+    #[tokio::test]
+    async fn resolves_latest_skipping_drafts_and_prereleases() {
+        let pages = vec![page(&[
+            release("v0.130.0", true, false),
+            release("v0.129.0", false, true),
+            release("v0.128.2", false, false),
+            release("not-a-version", false, false),
+            release("v0.128.1", false, false),
+        ])];
+        let (res, requested) = resolve(pages, None).await;
+        assert_eq!(res.unwrap(), Version::new(0, 128, 2));
+        assert_eq!(requested, [1]);
+    }
+
+    #[tokio::test]
+    async fn resolves_highest_version_in_range() {
+        let pages = vec![page(&[
+            release("v0.125.0", false, false),
+            release("v0.124.1", false, false),
+            release("v0.124.0", false, false),
+            release("v0.119.0", false, false),
+        ])];
+        let (res, _) = resolve(pages, Some(">=0.120, <0.125")).await;
+        assert_eq!(res.unwrap(), Version::new(0, 124, 1));
+    }
+
+    #[tokio::test]
+    async fn pages_until_a_match_is_found() {
+        let first = (0..RELEASES_PER_PAGE)
+            .map(|patch| release(&format!("v0.140.{}", patch), false, false))
+            .collect::<Vec<_>>();
+        let pages = vec![
+            page(&first),
+            page(&[
+                release("v0.125.0", false, true),
+                release("v0.124.0", false, false),
+            ]),
+            page(&[release("v0.123.0", false, false)]),
+        ];
+        let (res, requested) = resolve(pages, Some("<0.125")).await;
+        assert_eq!(res.unwrap(), Version::new(0, 124, 0));
+        assert_eq!(requested, [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn fails_when_no_version_matches() {
+        let first = (0..RELEASES_PER_PAGE)
+            .map(|patch| release(&format!("v0.140.{}", patch), false, false))
+            .collect::<Vec<_>>();
+        let pages = vec![page(&first), page(&[release("v0.130.0", false, false)])];
+        let (res, requested) = resolve(pages, Some("^0.100")).await;
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("没有满足要求的hugo版本"));
+        assert_eq!(requested, [1, 2]);
+    }
+
+    #[test]
+    fn recognises_cache_keys() {
+        assert!(is_cache_key("extended_0.128.0_linux-amd64"));
+        assert!(is_cache_key("standard_0.128.0_windows-arm64"));
+        assert!(is_cache_key("extended_withdeploy_0.137.1_darwin-universal"));
+        assert!(is_cache_key("extended_0.128.0-DEV_linux-amd64"));
+
+        assert!(!is_cache_key("extended_0.128.0_"));
+        assert!(!is_cache_key("extended_latest_linux-amd64"));
+        assert!(!is_cache_key("extended0.128.0_linux-amd64"));
+        assert!(!is_cache_key("dart-sass"));
+        assert!(!is_cache_key("my_site"));
+    }
+
+    #[test]
+    fn maps_platform_to_asset_suffix() {
+        let cases = [
+            ("linux", "x86_64", "linux-amd64.tar.gz"),
+            ("linux", "aarch64", "linux-arm64.tar.gz"),
+            ("macos", "x86_64", "darwin-universal.tar.gz"),
+            ("macos", "aarch64", "darwin-universal.tar.gz"),
+            ("windows", "x86_64", "windows-amd64.zip"),
+        ];
+        for edition in [
+            HugoEdition::Standard,
+            HugoEdition::Extended,
+            HugoEdition::ExtendedWithdeploy,
+        ] {
+            for (os, arch, suffix) in cases {
+                assert_eq!(platform_asset_suffix(edition, os, arch).unwrap(), suffix);
+            }
+        }
+
+        let standard = |os, arch| platform_asset_suffix(HugoEdition::Standard, os, arch);
+        assert_eq!(standard("linux", "arm").unwrap(), "linux-arm.tar.gz");
+        assert_eq!(standard("windows", "aarch64").unwrap(), "windows-arm64.zip");
+        assert_eq!(
+            standard("freebsd", "x86_64").unwrap(),
+            "freebsd-amd64.tar.gz"
+        );
+        assert_eq!(
+            standard("openbsd", "x86_64").unwrap(),
+            "openbsd-amd64.tar.gz"
+        );
+        assert!(standard("linux", "riscv64").is_err());
+        assert!(standard("solaris", "x86_64").is_err());
+    }
+
+    #[test]
+    fn rejects_extended_on_unsupported_platforms() {
+        for edition in [HugoEdition::Extended, HugoEdition::ExtendedWithdeploy] {
+            for (os, arch) in [
+                ("linux", "arm"),
+                ("windows", "aarch64"),
+                ("freebsd", "x86_64"),
+                ("netbsd", "x86_64"),
+                ("openbsd", "aarch64"),
+            ] {
+                let err = platform_asset_suffix(edition, os, arch).unwrap_err();
+                assert!(err.to_string().contains("edition改为standard"), "{}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::hugo::HugoEdition;
+use semver::Version;
+use std::{path::Path, str::FromStr};
+use tokio::process::Command;
+
+pub struct HugoVersionInfo {
+    pub version: Version,
+    pub edition: HugoEdition,
+    pub commit: Option<String>,
+    pub platform: Option<String>,
+    pub build_date: Option<String>,
+    pub vendor: Option<String>,
+}
+
+impl FromStr for HugoVersionInfo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        let token = tokens
+            .find(|token| {
+                token
+                    .strip_prefix('v')
+                    .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+            })
+            .ok_or(anyhow::anyhow!("无法解析hugo版本：{}", s.trim()))?;
+
+        let mut tags = token[1..].split(['+', '/']);
+        let mut parts = tags
+            .next()
+            .unwrap_or_default()
+            .split('-')
+            .collect::<Vec<_>>();
+        let commit = match parts.last() {
+            Some(last)
+                if parts.len() > 1
+                    && last.len() >= 7
+                    && last.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                parts.pop().map(str::to_owned)
+            }
+            _ => None,
+        };
+        let version = Version::parse(&parts.join("-"))?;
+        let edition = HugoEdition::from_build_tags(tags);
+
+        let mut info = Self {
+            version,
+            edition,
+            commit,
+            platform: None,
+            build_date: None,
+            vendor: None,
+        };
+
+        while let Some(token) = tokens.next() {
+            if let Some(build_date) = token.strip_prefix("BuildDate=") {
+                info.build_date = Some(build_date.to_owned());
+            } else if token == "BuildDate:" {
+                info.build_date = tokens.next().map(str::to_owned);
+            } else if let Some(vendor) = token.strip_prefix("VendorInfo=") {
+                info.vendor = Some(vendor.to_owned());
+            } else if token.contains('/') && info.platform.is_none() {
+                info.platform = Some(token.to_owned());
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+pub async fn hugo_version(hugo: &Path) -> Result<Option<(String, HugoVersionInfo)>, anyhow::Error> {
+    let Ok(output) = Command::new(hugo).arg("version").output().await else {
+        return Ok(None);
+    };
+    let status = output.status;
+
+    if status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        let info = stdout.parse()?;
+        Ok(Some((stdout, info)))
+    } else {
+        Err(anyhow::anyhow!(
+            "hugo version执行失败！退出码：{}",
+            if let Some(code) = status.code() {
+                code.to_string()
+            } else {
+                "None".into()
+            }
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(output: &str) -> HugoVersionInfo {
+        output.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_current_output() {
+        let info = parse(
+            "hugo v0.128.0-e6d94a4d3f7e0e8e2e4fe6d1d35e54a3cc2c7c42+extended linux/amd64 \
+             BuildDate=2024-06-25T16:13:27Z VendorInfo=gohugoio",
+        );
+
+        assert_eq!(info.version, Version::new(0, 128, 0));
+        assert_eq!(info.edition, HugoEdition::Extended);
+        assert_eq!(
+            info.commit.as_deref(),
+            Some("e6d94a4d3f7e0e8e2e4fe6d1d35e54a3cc2c7c42")
+        );
+        assert_eq!(info.platform.as_deref(), Some("linux/amd64"));
+        assert_eq!(info.build_date.as_deref(), Some("2024-06-25T16:13:27Z"));
+        assert_eq!(info.vendor.as_deref(), Some("gohugoio"));
+    }
+
+    #[test]
+    fn parses_extended_withdeploy() {
+        let info = parse(
+            "hugo v0.137.1-9a2ab6ef0eb0fd89b5c6a2a1f1a47bc82b9a3b3b+extended+withdeploy \
+             darwin/arm64 BuildDate=2024-11-04T16:04:06Z VendorInfo=gohugoio",
+        );
+
+        assert_eq!(info.version, Version::new(0, 137, 1));
+        assert_eq!(info.edition, HugoEdition::ExtendedWithdeploy);
+        assert_eq!(info.platform.as_deref(), Some("darwin/arm64"));
+    }
+
+    #[test]
+    fn parses_standard() {
+        let info = parse(
+            "hugo v0.128.0-e6d94a4d3f7e0e8e2e4fe6d1d35e54a3cc2c7c42 windows/amd64 \
+             BuildDate=2024-06-25T16:13:27Z VendorInfo=gohugoio",
+        );
+
+        assert_eq!(info.edition, HugoEdition::Standard);
+        assert_eq!(info.platform.as_deref(), Some("windows/amd64"));
+    }
+
+    #[test]
+    fn parses_legacy_output() {
+        let info = parse(
+            "Hugo Static Site Generator v0.55.6-A5D4C82D2/extended linux/amd64 \
+             BuildDate: 2019-05-18T11:08:01Z",
+        );
+
+        assert_eq!(info.version, Version::new(0, 55, 6));
+        assert_eq!(info.edition, HugoEdition::Extended);
+        assert_eq!(info.commit.as_deref(), Some("A5D4C82D2"));
+        assert_eq!(info.platform.as_deref(), Some("linux/amd64"));
+        assert_eq!(info.build_date.as_deref(), Some("2019-05-18T11:08:01Z"));
+        assert_eq!(info.vendor, None);
+    }
+
+    #[test]
+    fn parses_dev_build() {
+        let info = parse("hugo v0.129.0-DEV+extended linux/amd64 BuildDate=unknown");
+
+        assert_eq!(info.version, Version::parse("0.129.0-DEV").unwrap());
+        assert_eq!(info.edition, HugoEdition::Extended);
+        assert_eq!(info.commit, None);
+        assert_eq!(info.build_date.as_deref(), Some("unknown"));
+
+        let info = parse("hugo v0.129.0-DEV-1f2e3d4c5b+extended linux/amd64 BuildDate=unknown");
+
+        assert_eq!(info.version, Version::parse("0.129.0-DEV").unwrap());
+        assert_eq!(info.commit.as_deref(), Some("1f2e3d4c5b"));
+    }
+
+    #[test]
+    fn does_not_match_version_prefix() {
+        let info =
+            parse("hugo v0.128.0-e6d94a4d3f7e0e8e2e4fe6d1d35e54a3cc2c7c42+extended linux/amd64");
+        assert_ne!(info.version, Version::new(0, 12, 0));
+
+        let info =
+            parse("hugo v0.12.0-e6d94a4d3f7e0e8e2e4fe6d1d35e54a3cc2c7c42+extended linux/amd64");
+        assert_eq!(info.version, Version::new(0, 12, 0));
+        assert_ne!(info.version, Version::new(0, 128, 0));
+    }
+
+    #[test]
+    fn rejects_unparseable_output() {
+        assert!("".parse::<HugoVersionInfo>().is_err());
+        assert!("command not found".parse::<HugoVersionInfo>().is_err());
+        assert!("hugo vnext".parse::<HugoVersionInfo>().is_err());
+    }
+}
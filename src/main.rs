@@ -1,57 +1,136 @@
+mod dart_sass;
+mod download;
+mod generator;
+mod git;
+mod hugo;
+mod hugo_version;
 mod mem_probe;
 mod opendal_fs;
-
-use clap::Parser;
-use fs_extra::dir;
+mod release;
+
+use clap::{Parser, Subcommand};
+use dart_sass::{fetch_dart_sass, DartSassConfig};
+use fs_extra::dir::{self, CopyOptions};
+use generator::GeneratorConfig;
+use git::GitCredentials;
+use hugo::{cache_list, cache_prune, hugo_info, HugoConfig};
+use indexmap::IndexMap;
 use mem_probe::MemProbe;
-use opendal::{layers::MimeGuessLayer, services::Oss, Operator};
-use opendal_fs::{sync_dir, ConcurrentUploadTasks};
+use opendal::{layers::MimeGuessLayer, Operator, Scheme};
+use opendal_fs::{
+    path_to_target, plan_sync_dir, retry_layer, sync_dir, ConcurrentUploadTasks,
+    DEFAULT_CONCURRENCY,
+};
 use pushover_rs::{send_pushover_request, PushoverSound};
 use serde::Deserialize;
 use std::{
-    env::{self, current_exe, set_current_dir},
-    ffi::{OsStr, OsString},
-    io::Read,
-    path::{Path, PathBuf},
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    iter,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
 };
 use tokio::{
     fs::{self, remove_dir_all},
     process::Command,
-    task::spawn_blocking,
+    task::{spawn_blocking, JoinSet},
 };
+use tracing::Instrument;
 use tracing_subscriber::fmt::{format::FmtSpan, time::ChronoLocal};
 
 #[derive(Parser, Debug)]
 enum Commands {
     Start,
-    UpgradeHugo,
-    Run,
+    UpgradeHugo {
+        #[arg(long)]
+        update_lock: bool,
+    },
+    Run {
+        #[arg(long)]
+        dry_run: bool,
+        #[arg(long = "env")]
+        envs: Vec<String>,
+        #[arg(long)]
+        parallel: bool,
+    },
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    HugoInfo,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    List,
+    Prune {
+        #[arg(long)]
+        all: bool,
+    },
 }
 
 impl Commands {
     fn init() -> Self {
         let s = Self::parse();
-        match s {
+        match &s {
             Self::Start => tracing::info!("workflow-bin start"),
-            Self::UpgradeHugo => tracing::info!("workflow-bin upgrade-hugo"),
-            Self::Run => tracing::info!("workflow-bin run"),
+            Self::UpgradeHugo { update_lock } => tracing::info!(
+                "workflow-bin upgrade-hugo{}",
+                if *update_lock { " --update-lock" } else { "" }
+            ),
+            Self::Run {
+                dry_run,
+                envs,
+                parallel,
+            } => tracing::info!(
+                "workflow-bin run{}{}{}",
+                if *dry_run { " --dry-run" } else { "" },
+                envs.iter()
+                    .map(|env| format!(" --env {}", env))
+                    .collect::<String>(),
+                if *parallel { " --parallel" } else { "" }
+            ),
+            Self::Cache {
+                action: CacheAction::List,
+            } => tracing::info!("workflow-bin cache list"),
+            Self::Cache {
+                action: CacheAction::Prune { all },
+            } => tracing::info!(
+                "workflow-bin cache prune{}",
+                if *all { " --all" } else { "" }
+            ),
+            Self::HugoInfo => tracing::info!("workflow-bin hugo-info"),
         }
         s
     }
 
     fn is_start(&self) -> bool {
-        if let Self::Start = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Self::Start)
     }
 
     fn is_run(&self) -> bool {
-        if let Self::Run = self {
-            true
+        matches!(self, Self::Run { .. })
+    }
+
+    fn is_dry_run(&self) -> bool {
+        matches!(self, Self::Run { dry_run: true, .. })
+    }
+
+    fn is_parallel(&self) -> bool {
+        matches!(self, Self::Run { parallel: true, .. })
+    }
+
+    fn is_update_lock(&self) -> bool {
+        matches!(self, Self::UpgradeHugo { update_lock: true })
+    }
+
+    fn envs(&self) -> &[String] {
+        if let Self::Run { envs, .. } = self {
+            envs
         } else {
-            false
+            &[]
         }
     }
 }
@@ -69,17 +148,25 @@ fn env_var(key: impl AsRef<OsStr>) -> Result<String, anyhow::Error> {
 struct Pushover {
     user_key: String,
     app_token: String,
+    dry_run: bool,
 }
 
 impl Pushover {
-    fn new() -> Result<Self, anyhow::Error> {
+    fn new(dry_run: bool) -> Result<Self, anyhow::Error> {
         Ok(Self {
             user_key: env_var("PUSHOVER_USER_KEY")?,
             app_token: env_var("PUSHOVER_APP_TOKEN")?,
+            dry_run,
         })
     }
 
     async fn send(&self, message: &str, sound: PushoverSound) -> Result<(), anyhow::Error> {
+        if self.dry_run {
+            tracing::info!("[dry-run] 将发送Pushover消息：{}", message);
+            tracing::info!("[dry-run] Pushover音色：{}", sound);
+            return Ok(());
+        }
+
         tracing::info!("正在发送Pushover消息：{}", message);
         tracing::info!("Pushover音色：{}", sound);
 
@@ -99,12 +186,14 @@ impl Pushover {
     }
 }
 
+// Not FmtSpan::FULL: the env/target spans are attached with `instrument`, so ENTER/EXIT would
+// log a line on every poll of every deploy task.
 #[cfg(debug_assertions)]
 pub fn install_tracing() {
     tracing_subscriber::fmt()
         .with_timer(ChronoLocal::new("%m-%d %H:%M:%S".into()))
         .with_max_level(tracing::Level::DEBUG)
-        .with_span_events(FmtSpan::FULL)
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
         .with_thread_names(true)
         .init();
 }
@@ -113,14 +202,44 @@ pub fn install_tracing() {
 pub fn install_tracing() {
     tracing_subscriber::fmt()
         .with_timer(ChronoLocal::new("%m-%d %H:%M:%S".into()))
-        .with_span_events(FmtSpan::FULL)
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
         .with_thread_names(true)
         .init();
 }
 
 #[derive(Deserialize)]
-struct HugoConfig {
-    version: String,
+#[serde(untagged)]
+enum OptionValue {
+    Literal(String),
+    Env { env: String },
+}
+
+impl OptionValue {
+    fn env(key: &str) -> Self {
+        Self::Env {
+            env: key.to_owned(),
+        }
+    }
+
+    fn resolve(&self) -> Result<String, anyhow::Error> {
+        match self {
+            Self::Literal(value) => Ok(value.clone()),
+            Self::Env { env } => env_var(env),
+        }
+    }
+
+    fn is_secret(&self) -> bool {
+        matches!(self, Self::Env { .. })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FlagValue {
+    Switch(bool),
+    Integer(i64),
+    Float(f64),
+    Value(OptionValue),
 }
 
 #[derive(Deserialize)]
@@ -128,190 +247,410 @@ struct GithubDeployConfig {
     username: String,
     org: String,
     repo: String,
-    access_token: Option<String>,
-    user_email: Option<String>,
-    user_name: Option<String>,
+    #[serde(default = "GithubDeployConfig::default_access_token")]
+    access_token: OptionValue,
+    #[serde(default = "GithubDeployConfig::default_user_email")]
+    user_email: OptionValue,
+    #[serde(default = "GithubDeployConfig::default_user_name")]
+    user_name: OptionValue,
+    #[serde(default = "GithubDeployConfig::default_depth")]
+    depth: u32,
+}
+
+impl GithubDeployConfig {
+    fn default_access_token() -> OptionValue {
+        OptionValue::env("DEPLOY_GITHUB_ACCESS_TOKEN")
+    }
+
+    fn default_user_email() -> OptionValue {
+        OptionValue::env("DEPLOY_GITHUB_USER_EMAIL")
+    }
+
+    fn default_user_name() -> OptionValue {
+        OptionValue::env("DEPLOY_GITHUB_USER_NAME")
+    }
+
+    fn default_depth() -> u32 {
+        1
+    }
 }
 
 #[derive(Deserialize)]
-struct OssSyncConfig {
+struct SyncConfig {
     root: String,
     files: Vec<String>,
     dirs: Vec<String>,
 }
 
 #[derive(Deserialize)]
-struct OssDeployConfig {
-    sync: OssSyncConfig,
-    access_key_id: Option<String>,
-    access_key_secret: Option<String>,
+struct StorageDeployConfig {
+    service: String,
+    sync: SyncConfig,
+    concurrency: Option<usize>,
+    max_retries: Option<usize>,
+    max_retry_delay_secs: Option<u64>,
+    #[serde(default)]
+    options: HashMap<String, OptionValue>,
 }
 
-#[derive(Deserialize)]
-struct DeployConfig {
-    github: GithubDeployConfig,
-    oss: OssDeployConfig,
+impl StorageDeployConfig {
+    fn legacy_oss(sync: SyncConfig) -> Self {
+        let options = [
+            ("access_key_id", "OSS_ACCESS_KEY_ID"),
+            ("access_key_secret", "OSS_ACCESS_KEY_SECRET"),
+            ("bucket", "OSS_PROD_BUCKET"),
+            ("endpoint", "OSS_PROD_ENDPOINT"),
+        ];
+
+        Self {
+            service: "oss".to_owned(),
+            sync,
+            concurrency: None,
+            max_retries: None,
+            max_retry_delay_secs: None,
+            options: options
+                .into_iter()
+                .map(|(key, env)| (key.to_owned(), OptionValue::env(env)))
+                .collect(),
+        }
+    }
+
+    fn operator(&self, env: &EnvironmentConfig) -> Result<Operator, anyhow::Error> {
+        let scheme = Scheme::from_str(&self.service)?;
+
+        let mut options = HashMap::new();
+        options.insert("root".to_owned(), self.sync.root.clone());
+        for (key, value) in self.options.iter().chain(&env.storage) {
+            options.insert(key.clone(), value.resolve()?);
+        }
+
+        Ok(Operator::via_iter(scheme, options)?
+            .layer(MimeGuessLayer::default())
+            .layer(retry_layer(self.max_retries, self.max_retry_delay_secs)))
+    }
 }
 
-#[derive(Deserialize)]
-struct WorkflowConfig {
-    hugo: HugoConfig,
-    deploy: DeployConfig,
+#[derive(Default, Deserialize)]
+struct EnvironmentConfig {
+    base_url: Option<OptionValue>,
+    #[serde(default, alias = "hugo_args")]
+    args: Vec<String>,
+    #[serde(default)]
+    drafts: bool,
+    #[serde(default)]
+    flags: IndexMap<String, FlagValue>,
+    #[serde(default, rename = "env")]
+    env_vars: IndexMap<String, OptionValue>,
+    output_dir: Option<PathBuf>,
+    branch: Option<String>,
+    #[serde(default)]
+    storage: HashMap<String, OptionValue>,
 }
 
-impl WorkflowConfig {
-    async fn read() -> Result<Self, anyhow::Error> {
-        tracing::info!("正在读取workflow.toml……");
-        Ok(toml::from_str(&fs::read_to_string("workflow.toml").await?)?)
+impl EnvironmentConfig {
+    /// The draft and production builds used before `[environments]` existed.
+    fn legacy_defaults(legacy_oss: bool) -> IndexMap<String, Self> {
+        let mut draft = Self {
+            base_url: Some(OptionValue::env("HUGO_DRAFT_BASE_URL")),
+            drafts: true,
+            branch: Some("draft".to_owned()),
+            ..Default::default()
+        };
+        if legacy_oss {
+            draft.storage = HashMap::from([
+                ("bucket".to_owned(), OptionValue::env("OSS_DRAFT_BUCKET")),
+                (
+                    "endpoint".to_owned(),
+                    OptionValue::env("OSS_DRAFT_ENDPOINT"),
+                ),
+            ]);
+        }
+
+        IndexMap::from([
+            ("draft".to_owned(), draft),
+            ("production".to_owned(), Self::default()),
+        ])
+    }
+
+    fn output_dir(&self, env_name: &str) -> PathBuf {
+        match &self.output_dir {
+            Some(output_dir) => output_dir.clone(),
+            None => Path::new("public").join(env_name),
+        }
     }
 }
 
-fn retain_decimal_places(f: f64, n: i32) -> f64 {
-    let power = 10.0f64.powi(n);
-    (f * power).round() / power
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum DeployTargetKind {
+    Git(GithubDeployConfig),
+    Storage(StorageDeployConfig),
 }
 
-#[cfg(windows)]
-fn unzip(z: &[u8]) -> Result<(OsString, Vec<u8>), anyhow::Error> {
-    use std::io::Cursor;
-    use zip::ZipArchive;
+#[derive(Deserialize)]
+struct DeployTarget {
+    name: String,
+    environments: Option<Vec<String>>,
+    #[serde(flatten)]
+    kind: DeployTargetKind,
+}
 
-    let mut archive = ZipArchive::new(Cursor::new(z))?;
+impl DeployTarget {
+    fn applies_to(&self, env_name: &str) -> bool {
+        match &self.environments {
+            Some(environments) => environments.iter().any(|name| name == env_name),
+            None => true,
+        }
+    }
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let path = file
-            .enclosed_name()
-            .ok_or(anyhow::anyhow!("压缩文件路径异常！"))?;
-        let name = path
-            .file_name()
-            .ok_or(anyhow::anyhow!("压缩文件名异常！"))?;
+    fn validate(&self, env: &EnvironmentConfig) -> Result<(), anyhow::Error> {
+        match &self.kind {
+            DeployTargetKind::Git(config) => {
+                config.access_token.resolve()?;
+                config.user_email.resolve()?;
+                config.user_name.resolve()?;
+            }
+            DeployTargetKind::Storage(config) => {
+                config.operator(env)?;
+            }
+        }
+        Ok(())
+    }
 
-        if name
-            .to_str()
-            .ok_or(anyhow::anyhow!("压缩文件名编码异常！"))?
-            .starts_with("hugo")
-        {
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents)?;
-            return Ok((name.to_owned(), contents));
+    async fn deploy(
+        &self,
+        output: &Path,
+        env_name: &str,
+        env: &EnvironmentConfig,
+        dry_run: bool,
+    ) -> Result<(), anyhow::Error> {
+        tracing::info!("正在deploy目标：{}", self.name);
+        match &self.kind {
+            DeployTargetKind::Git(config) => {
+                deploy_github(config, output, env_name, env, dry_run).await
+            }
+            DeployTargetKind::Storage(config) => {
+                deploy_storage(config, output, env_name, env, dry_run).await
+            }
         }
     }
+}
+
+#[derive(Deserialize)]
+struct LegacyOssDeployConfig {
+    sync: SyncConfig,
+}
+
+#[derive(Deserialize)]
+struct DeployToml {
+    #[serde(default)]
+    parallel: bool,
+    #[serde(default)]
+    targets: Vec<DeployTarget>,
+    github: Option<GithubDeployConfig>,
+    oss: Option<LegacyOssDeployConfig>,
+}
 
-    Err(anyhow::anyhow!("压缩包中未找到hugo执行文件！"))
+#[derive(Deserialize)]
+#[serde(from = "DeployToml")]
+struct DeployConfig {
+    parallel: bool,
+    targets: Vec<DeployTarget>,
+    legacy_oss: bool,
 }
 
-#[cfg(not(windows))]
-fn unzip(z: &[u8]) -> Result<(OsString, Vec<u8>), anyhow::Error> {
-    use flate2::read::GzDecoder;
-    use tar::Archive;
+impl From<DeployToml> for DeployConfig {
+    fn from(toml: DeployToml) -> Self {
+        let mut targets = toml.targets;
+        let legacy_oss = toml.oss.is_some();
 
-    for entry in Archive::new(GzDecoder::new(z)).entries()? {
-        let mut file = entry?;
-        let path = file.path()?.into_owned();
-        let name = path
-            .file_name()
-            .ok_or(anyhow::anyhow!("压缩文件名异常！"))?;
+        if let Some(github) = toml.github {
+            tracing::warn!(
+                "[deploy.github]已弃用，将作为kind为git的目标处理，请改用[[deploy.targets]]"
+            );
+            targets.push(DeployTarget {
+                name: "github".to_owned(),
+                environments: None,
+                kind: DeployTargetKind::Git(github),
+            });
+        }
 
-        if name
-            .to_str()
-            .ok_or(anyhow::anyhow!("压缩文件名编码异常！"))?
-            .starts_with("hugo")
-        {
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents)?;
-            return Ok((name.to_owned(), contents));
+        if let Some(oss) = toml.oss {
+            tracing::warn!(
+                "[deploy.oss]已弃用，将作为service为oss的storage目标处理（默认使用OSS_PROD_BUCKET和OSS_PROD_ENDPOINT，可在环境的storage中覆盖），请改用[[deploy.targets]]"
+            );
+            targets.push(DeployTarget {
+                name: "oss".to_owned(),
+                environments: None,
+                kind: DeployTargetKind::Storage(StorageDeployConfig::legacy_oss(oss.sync)),
+            });
         }
-    }
 
-    Err(anyhow::anyhow!("压缩包中未找到hugo执行文件！"))
+        Self {
+            parallel: toml.parallel,
+            targets,
+            legacy_oss,
+        }
+    }
 }
 
-#[cfg(not(windows))]
-async fn chmod_exec(path: impl AsRef<std::path::Path>) -> Result<(), anyhow::Error> {
-    tracing::info!("正在设置执行权限……");
-    use std::{fs::Permissions, os::unix::prelude::PermissionsExt};
-    Ok(fs::set_permissions(path, Permissions::from_mode(0o755)).await?)
+#[derive(Deserialize)]
+struct WorkflowToml {
+    generator: Option<GeneratorConfig>,
+    hugo: Option<HugoConfig>,
+    dart_sass: Option<DartSassConfig>,
+    environments: Option<IndexMap<String, EnvironmentConfig>>,
+    deploy: DeployConfig,
 }
 
-async fn fetch_hugo(config: HugoConfig) -> Result<PathBuf, anyhow::Error> {
-    let version = config.version;
+#[derive(Deserialize)]
+#[serde(try_from = "WorkflowToml")]
+struct WorkflowConfig {
+    generator: GeneratorConfig,
+    dart_sass: Option<DartSassConfig>,
+    environments: IndexMap<String, EnvironmentConfig>,
+    deploy: DeployConfig,
+}
 
-    tracing::info!("请求的hugo版本是：{}", version);
-    tracing::info!("正在校验现有hugo版本……");
+impl TryFrom<WorkflowToml> for WorkflowConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(toml: WorkflowToml) -> Result<Self, Self::Error> {
+        let generator = match (toml.generator, toml.hugo) {
+            (Some(generator), None) => generator,
+            (None, Some(hugo)) => GeneratorConfig::Hugo(hugo),
+            (Some(_), Some(_)) => return Err(anyhow::anyhow!("[generator]与[hugo]不能同时指定！")),
+            (None, None) => return Err(anyhow::anyhow!("缺少[generator]！")),
+        };
+
+        let environments = match toml.environments {
+            Some(environments) => environments,
+            None => {
+                tracing::warn!(
+                    "缺少[environments]，将使用默认的draft和production环境（draft使用HUGO_DRAFT_BASE_URL、草稿构建及draft分支）"
+                );
+                EnvironmentConfig::legacy_defaults(toml.deploy.legacy_oss)
+            }
+        };
 
-    let exe = current_exe()?;
-    let hugo = exe.with_file_name("hugo");
-    let mut need_fetch = true;
+        Ok(Self {
+            generator,
+            dart_sass: toml.dart_sass,
+            environments,
+            deploy: toml.deploy,
+        })
+    }
+}
 
-    if let Ok(output) = Command::new(&hugo).arg("version").output().await {
-        let status = output.status;
+impl WorkflowConfig {
+    async fn read() -> Result<Self, anyhow::Error> {
+        tracing::info!("正在读取workflow.toml……");
+        let config: Self = toml::from_str(&fs::read_to_string("workflow.toml").await?)?;
+
+        for target in &config.deploy.targets {
+            for name in target.environments.iter().flatten() {
+                if !config.environments.contains_key(name) {
+                    return Err(anyhow::anyhow!(
+                        "目标{}引用了不存在的环境：{}",
+                        target.name,
+                        name
+                    ));
+                }
+            }
+        }
 
-        if status.success() {
-            if output
-                .stdout
-                .starts_with(format!("hugo v{}", version).as_bytes())
-            {
-                need_fetch = false;
-                tracing::info!("现有hugo版本匹配！将跳过下载");
-            } else {
-                tracing::info!("现有hug版本不匹配，准备更新hugo");
+        let cwd = env::current_dir()?;
+        let mut outputs: Vec<(&str, PathBuf)> = Vec::new();
+        for (env_name, env) in &config.environments {
+            let output = normalize_path(&cwd.join(env.output_dir(env_name)));
+            if cwd.starts_with(&output) {
+                return Err(anyhow::anyhow!(
+                    "环境{}的output_dir不能是当前目录或其上级目录：{}",
+                    env_name,
+                    output.display()
+                ));
             }
-        } else {
-            return Err(anyhow::anyhow!(
-                "hugo version执行失败！退出码：{}",
-                if let Some(code) = status.code() {
-                    code.to_string()
-                } else {
-                    "None".into()
+            for (other_name, other) in &outputs {
+                if output.starts_with(other) || other.starts_with(&output) {
+                    return Err(anyhow::anyhow!(
+                        "环境{}与{}的output_dir重复或相互嵌套：{}",
+                        other_name,
+                        env_name,
+                        output.display()
+                    ));
                 }
-            ));
+            }
+            outputs.push((env_name, output));
         }
-    } else {
-        tracing::info!("hugo不存在，准备下载hugo");
-    }
-
-    if need_fetch {
-        #[cfg(target_os = "macos")]
-        const SUFFIX: &str = "darwin-universal.tar.gz";
-        #[cfg(target_os = "linux")]
-        const SUFFIX: &str = "Linux-64bit.tar.gz";
-        #[cfg(target_os = "windows")]
-        const SUFFIX: &str = "windows-amd64.zip";
 
-        let url = format!(
-            "https://github.com/gohugoio/hugo/releases/download/v{}/hugo_extended_{}_{}",
-            version, version, SUFFIX
-        );
-        tracing::info!("正在GET：{}", url);
+        Ok(config)
+    }
 
-        let bytes = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    fn select_environments(
+        &self,
+        names: &[String],
+    ) -> Result<Vec<(&str, &EnvironmentConfig)>, anyhow::Error> {
+        if names.is_empty() {
+            return Ok(self
+                .environments
+                .iter()
+                .map(|(name, env)| (name.as_str(), env))
+                .collect());
+        }
 
-        if bytes.is_empty() {
-            return Err(anyhow::anyhow!("未下载任何内容！"));
-        } else {
-            tracing::info!(
-                "已下载：{} MB",
-                retain_decimal_places(bytes.len() as f64 / 1024.0 / 1024.0, 3)
-            );
-            tracing::info!("正在解压……");
+        names
+            .iter()
+            .map(|name| match self.environments.get_key_value(name) {
+                Some((name, env)) => Ok((name.as_str(), env)),
+                None => Err(anyhow::anyhow!("环境不存在：{}", name)),
+            })
+            .collect()
+    }
 
-            let (name, contents) = unzip(&bytes)?;
-            tracing::info!(
-                "正在保存：{:?}（{} MB）",
-                name,
-                retain_decimal_places(contents.len() as f64 / 1024.0 / 1024.0, 3)
-            );
+    fn validate(&self, envs: &[(&str, &EnvironmentConfig)]) -> Result<(), anyhow::Error> {
+        tracing::info!("正在校验配置……");
+
+        for (env_name, env) in envs {
+            env.base_url
+                .iter()
+                .chain(env.env_vars.values())
+                .chain(env.flags.values().filter_map(|value| match value {
+                    FlagValue::Value(value) => Some(value),
+                    _ => None,
+                }))
+                .try_for_each(|value| value.resolve().map(drop))
+                .map_err(|err| anyhow::anyhow!("环境{}：{}", env_name, err))?;
+
+            for target in &self.deploy.targets {
+                if target.applies_to(env_name) {
+                    target
+                        .validate(env)
+                        .map_err(|err| anyhow::anyhow!("目标{}：{}", target.name, err))?;
+                }
+            }
+        }
 
-            let path = exe.with_file_name(name);
-            fs::write(&path, contents).await?;
+        Ok(())
+    }
+}
 
-            #[cfg(not(windows))]
-            chmod_exec(path).await?;
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
         }
     }
+    normalized
+}
 
-    Ok(hugo)
+fn retain_decimal_places(f: f64, n: i32) -> f64 {
+    let power = 10.0f64.powi(n);
+    (f * power).round() / power
 }
 
 async fn spawn_command(cmd: &mut Command, hint: &str) -> Result<(), anyhow::Error> {
@@ -332,181 +671,365 @@ async fn spawn_command(cmd: &mut Command, hint: &str) -> Result<(), anyhow::Erro
     }
 }
 
-async fn remove_public() -> Result<(), anyhow::Error> {
-    let public = Path::new("public");
-    if public.is_dir() {
-        tracing::info!("正在清理public目录……");
-        remove_dir_all(public).await?;
+/// Written into every output directory after a build, so that only directories this tool
+/// produced are ever cleaned. It is never deployed.
+const OUTPUT_MARKER: &str = ".workflow-output";
+
+async fn remove_dir(dir: &Path) -> Result<(), anyhow::Error> {
+    if dir.is_dir() {
+        tracing::info!("正在清理{}目录……", dir.display());
+        remove_dir_all(dir).await?;
+    }
+    Ok(())
+}
+
+fn check_output(output: &Path) -> Result<(), anyhow::Error> {
+    if output.is_dir() && !output.join(OUTPUT_MARKER).is_file() {
+        return Err(anyhow::anyhow!(
+            "{}目录不是由workflow-bin构建的（缺少{}），拒绝清理！请确认output_dir配置，或手动删除该目录",
+            output.display(),
+            OUTPUT_MARKER
+        ));
     }
     Ok(())
 }
 
+async fn remove_output(output: &Path) -> Result<(), anyhow::Error> {
+    check_output(output)?;
+    remove_dir(output).await
+}
+
+async fn mark_output(output: &Path) -> Result<(), anyhow::Error> {
+    fs::create_dir_all(output).await?;
+    Ok(fs::write(output.join(OUTPUT_MARKER), "").await?)
+}
+
 async fn copy_dir<P, Q>(from: P, to: Q) -> Result<u64, anyhow::Error>
 where
     P: AsRef<Path> + Send + 'static,
     Q: AsRef<Path> + Send + 'static,
 {
-    Ok(spawn_blocking(move || dir::copy(from, to, &Default::default())).await??)
+    Ok(
+        spawn_blocking(move || dir::copy(from, to, &CopyOptions::new().content_only(true)))
+            .await??,
+    )
 }
 
-async fn deploy_github(config: &GithubDeployConfig, for_draft: bool) -> Result<(), anyhow::Error> {
-    tracing::info!(
-        "正在deploy github {}",
-        if for_draft { "draft" } else { "main" }
-    );
+async fn deploy_github(
+    config: &GithubDeployConfig,
+    output: &Path,
+    env_name: &str,
+    env: &EnvironmentConfig,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    tracing::info!("正在deploy github {}", env_name);
 
     let repo = &config.repo;
-    let access_token = config.access_token.as_ref().unwrap();
-    let url = format!(
-        "https://{}:{}@github.com/{}/{}.git",
-        config.username, access_token, config.org, repo
-    );
-
-    tracing::info!("正在执行：git clone {}", url.replace(access_token, "****"));
-    spawn_command(Command::new("git").arg("clone").arg(url), "git").await?;
-    set_current_dir(repo)?;
-
-    tracing::info!("正在配置git环境……");
-    spawn_command(
-        Command::new("git")
-            .arg("config")
-            .arg("user.email")
-            .arg(config.user_email.as_ref().unwrap()),
-        "git",
-    )
-    .await?;
-    spawn_command(
-        Command::new("git")
-            .arg("config")
-            .arg("user.name")
-            .arg(config.user_name.as_ref().unwrap()),
-        "git",
-    )
-    .await?;
-
-    if for_draft {
-        tracing::info!("正在执行：git checkout draft");
-        spawn_command(Command::new("git").arg("checkout").arg("draft"), "git").await?;
+    let repo_dir = Path::new(".deploy")
+        .join(env_name)
+        .join(format!("{}_{}", config.org, repo));
+    let credentials = GitCredentials {
+        username: config.username.clone(),
+        password: config.access_token.resolve()?,
+    };
+    let user_email = config.user_email.resolve()?;
+    let user_name = config.user_name.resolve()?;
+    let url = format!("https://github.com/{}/{}.git", config.org, repo);
+    let branch = env.branch.clone();
+    let depth = config.depth;
+
+    if dry_run {
+        tracing::info!("[dry-run] 将克隆：{} -> {}", url, repo_dir.display());
+        match &branch {
+            Some(branch) => tracing::info!("[dry-run] 将只克隆{}分支（深度：{}）", branch, depth),
+            None => tracing::info!("[dry-run] 将只克隆默认分支（深度：{}）", depth),
+        }
+        tracing::info!(
+            "[dry-run] 将清理{}/public目录并拷贝{}目录",
+            repo_dir.display(),
+            output.display()
+        );
+        tracing::info!("[dry-run] 将以{} <{}>提交：Deploy", user_name, user_email);
+        tracing::info!("[dry-run] 将推送（仅当有可以提交的内容时）");
+        tracing::info!("[dry-run] 将清理{}目录", repo_dir.display());
+        return Ok(());
     }
 
-    remove_public().await?;
-
-    tracing::info!("正在拷贝public目录……");
-    copy_dir("../public", "").await?;
-
-    tracing::info!("正在提交……");
-    spawn_command(Command::new("git").arg("add").arg("."), "git").await?;
-
-    if Command::new("git")
-        .arg("commit")
-        .arg("-m")
-        .arg("Deploy")
-        .spawn()?
-        .wait()
-        .await?
-        .success()
-    {
-        tracing::info!("正在执行：git push");
-        spawn_command(Command::new("git").arg("push"), "git").await?;
-    } else {
-        tracing::warn!("没有可以提交的内容！");
-    }
+    remove_dir(&repo_dir).await?;
+    fs::create_dir_all(&repo_dir).await?;
+    let span = tracing::Span::current();
+    let repository = spawn_blocking({
+        let repo_dir = repo_dir.clone();
+        let credentials = credentials.clone();
+        move || {
+            span.in_scope(|| {
+                tracing::info!("正在克隆：{}", url);
+                git::clone(&url, &repo_dir, branch.as_deref(), depth, &credentials)
+            })
+        }
+    })
+    .await??;
+
+    let public = repo_dir.join("public");
+    remove_dir(&public).await?;
+
+    tracing::info!("正在拷贝{}目录……", output.display());
+    copy_dir(output.to_owned(), public.clone()).await?;
+    fs::remove_file(public.join(OUTPUT_MARKER)).await?;
+
+    let span = tracing::Span::current();
+    spawn_blocking(move || {
+        span.in_scope(|| {
+            tracing::info!("正在提交……");
+            if git::commit_all(&repository, &user_name, &user_email, "Deploy")? {
+                tracing::info!("正在推送……");
+                git::push(&repository, &credentials)
+            } else {
+                tracing::warn!("没有可以提交的内容！");
+                Ok(())
+            }
+        })
+    })
+    .await??;
 
-    tracing::info!("正在清理{}目录……", repo);
-    set_current_dir("..")?;
-    Ok(remove_dir_all(repo).await?)
+    tracing::info!("正在清理{}目录……", repo_dir.display());
+    Ok(remove_dir_all(repo_dir).await?)
 }
 
-async fn deploy_oss(config: &OssDeployConfig, for_draft: bool) -> Result<(), anyhow::Error> {
-    tracing::info!(
-        "正在deploy oss {}",
-        if for_draft { "draft" } else { "prod" }
-    );
-    set_current_dir("public")?;
+async fn deploy_storage(
+    config: &StorageDeployConfig,
+    output: &Path,
+    env_name: &str,
+    env: &EnvironmentConfig,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    tracing::info!("正在deploy {} {}", config.service, env_name);
 
     tracing::info!("正在初始化Operator……");
     let sync = &config.sync;
-    let oss = Oss::default()
-        .root(&sync.root)
-        .access_key_id(config.access_key_id.as_ref().unwrap())
-        .access_key_secret(config.access_key_secret.as_ref().unwrap());
-    let oss = if for_draft {
-        oss.bucket(&env_var("OSS_DRAFT_BUCKET")?)
-            .endpoint(&env_var("OSS_DRAFT_ENDPOINT")?)
-    } else {
-        oss.bucket(&env_var("OSS_PROD_BUCKET")?)
-            .endpoint(&env_var("OSS_PROD_ENDPOINT")?)
-    };
+    let op = config.operator(env)?;
 
-    let op = Operator::new(oss)?
-        .layer(MimeGuessLayer::default())
-        .finish();
+    if dry_run {
+        for file in &sync.files {
+            tracing::info!("[dry-run] 将上传：{}", file);
+        }
+        for dir in &sync.dirs {
+            tracing::info!("[dry-run] 将同步目录：{}", dir);
+            if !output.join(dir).is_dir() {
+                tracing::warn!(
+                    "[dry-run] {}不存在，无法预览同步差异",
+                    output.join(dir).display()
+                );
+                continue;
+            }
+            let plan = plan_sync_dir(&op, output, dir).await?;
+            for path in &plan.uploads {
+                tracing::info!("[dry-run] 将上传：{}", path_to_target(path)?);
+            }
+            for target in &plan.deletes {
+                tracing::info!("[dry-run] 将删除：{}", target);
+            }
+            tracing::info!(
+                "[dry-run] 将上传：{}个，跳过：{}个，删除：{}个",
+                plan.uploads.len(),
+                plan.skipped,
+                plan.deletes.len()
+            );
+        }
+        return Ok(());
+    }
 
     tracing::info!("开始上传文件……");
-    let mut files = ConcurrentUploadTasks::new(op.clone());
+    let concurrency = config.concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+    let mut files = ConcurrentUploadTasks::new(op.clone(), output, concurrency);
     files.push_str_seq(&sync.files).await?;
     files.join().await?;
 
     tracing::info!("开始同步目录……");
     for dir in &sync.dirs {
         tracing::info!("正在同步目录：{}", dir);
-        sync_dir(&op, dir).await?;
+        let plan = sync_dir(&op, output, dir, concurrency).await?;
+        tracing::info!(
+            "已上传：{}个，跳过：{}个，删除：{}个",
+            plan.uploads.len(),
+            plan.skipped,
+            plan.deletes.len()
+        );
+    }
+
+    Ok(())
+}
+
+struct Workflow {
+    config: WorkflowConfig,
+    binary: PathBuf,
+    dart_sass: Option<PathBuf>,
+    dry_run: bool,
+    parallel: bool,
+}
+
+async fn join_all(mut set: JoinSet<Result<(), anyhow::Error>>) -> Result<(), anyhow::Error> {
+    let mut errors = Vec::new();
+
+    while let Some(res) = set.join_next().await {
+        if let Err(err) = res.map_err(anyhow::Error::from).and_then(|res| res) {
+            tracing::error!("{:#}", err);
+            errors.push(format!("{:#}", err));
+        }
     }
 
-    Ok(set_current_dir("..")?)
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "{}个任务失败：\r\n{}",
+            errors.len(),
+            errors.join("\r\n")
+        ))
+    }
 }
 
-async fn hugo_deploy(
-    hugo: impl AsRef<OsStr>,
-    config: &DeployConfig,
-    for_draft: bool,
+async fn deploy_target(
+    workflow: Arc<Workflow>,
+    index: usize,
+    env_name: String,
+    output: PathBuf,
 ) -> Result<(), anyhow::Error> {
-    tracing::info!(
-        "正在hugo deploy {}版本……",
-        if for_draft { "draft" } else { "production" }
-    );
-
-    remove_public().await?;
-
-    let mut hugo = Command::new(hugo);
-    let (hugo, base_url) = if for_draft {
-        let base_url = env_var("HUGO_DRAFT_BASE_URL")?;
-        (
-            hugo.arg("-b").arg(&base_url).arg("-D").arg("-F"),
-            Some(base_url),
-        )
+    let config = &workflow.config;
+    let target = &config.deploy.targets[index];
+    let env = &config.environments[&env_name];
+
+    target
+        .deploy(&output, &env_name, env, workflow.dry_run)
+        .await
+        .map_err(|err| anyhow::anyhow!("目标{}：{}", target.name, err))
+}
+
+async fn build_deploy(workflow: Arc<Workflow>, env_name: String) -> Result<(), anyhow::Error> {
+    let config = &workflow.config;
+    let generator = &config.generator;
+    let env = &config.environments[&env_name];
+    let env_name = env_name.as_str();
+    let dart_sass = workflow.dart_sass.as_deref();
+
+    let name = generator.name();
+    tracing::info!("正在{}构建并deploy {}版本……", name, env_name);
+
+    let output = env.output_dir(env_name);
+    if workflow.dry_run {
+        check_output(&output)?;
+        tracing::info!("[dry-run] 将清理{}目录", output.display());
     } else {
-        (&mut hugo, None)
-    };
+        remove_output(&output).await?;
+    }
+
+    let base_url = env
+        .base_url
+        .as_ref()
+        .map(OptionValue::resolve)
+        .transpose()?;
+    let mut build =
+        generator.build_command(&workflow.binary, base_url.as_deref(), env.drafts, &output);
+    if let Some(dart_sass) = dart_sass {
+        let path = env::var_os("PATH").unwrap_or_default();
+        build.env(
+            "PATH",
+            env::join_paths(iter::once(dart_sass.to_owned()).chain(env::split_paths(&path)))?,
+        );
+    }
+    let mut secrets = Vec::from_iter(base_url.clone());
+    for (flag, value) in &env.flags {
+        match value {
+            FlagValue::Switch(true) => {
+                build.arg(format!("--{}", flag));
+            }
+            FlagValue::Switch(false) => {}
+            FlagValue::Integer(value) => {
+                build.arg(format!("--{}", flag)).arg(value.to_string());
+            }
+            FlagValue::Float(value) => {
+                build.arg(format!("--{}", flag)).arg(value.to_string());
+            }
+            FlagValue::Value(value) => {
+                let resolved = value.resolve()?;
+                if value.is_secret() {
+                    secrets.push(resolved.clone());
+                }
+                build.arg(format!("--{}", flag)).arg(resolved);
+            }
+        }
+    }
+    build.args(&env.args);
+    for (key, value) in &env.env_vars {
+        let resolved = value.resolve()?;
+        if value.is_secret() {
+            tracing::info!("设置环境变量：{}=****", key);
+            secrets.push(resolved.clone());
+        } else {
+            tracing::info!("设置环境变量：{}={}", key, resolved);
+        }
+        build.env(key, resolved);
+    }
 
-    if let Some(base_url) = base_url {
+    let mut args = build
+        .as_std()
+        .get_args()
+        .collect::<Vec<&OsStr>>()
+        .join(" ".as_ref())
+        .to_string_lossy()
+        .into_owned();
+    for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+        args = args.replace(secret, "****");
+    }
+    if workflow.dry_run {
+        tracing::info!("[dry-run] 将执行：{} {}", name, args);
         tracing::info!(
-            "正在执行：hugo {}",
-            hugo.as_std()
-                .get_args()
-                .collect::<Vec<&OsStr>>()
-                .join(" ".as_ref())
-                .to_string_lossy()
-                .replace(&base_url, "****")
+            "[dry-run] 将使用{}目录中已有的构建结果预览deploy",
+            output.display()
         );
     } else {
-        tracing::info!("正在执行：hugo");
+        tracing::info!("正在执行：{} {}", name, args);
+        spawn_command(&mut build, name).await?;
+        mark_output(&output).await?;
     }
-    spawn_command(hugo, "hugo").await?;
 
-    deploy_github(&config.github, for_draft).await?;
-    deploy_oss(&config.oss, for_draft).await
+    let targets = config
+        .deploy
+        .targets
+        .iter()
+        .enumerate()
+        .filter(|(_, target)| target.applies_to(env_name))
+        .collect::<Vec<_>>();
+    let mut set = JoinSet::new();
+
+    if targets.is_empty() {
+        tracing::warn!("没有需要deploy的目标！");
+    }
+
+    for (index, target) in targets {
+        let span = tracing::info_span!("target", name = %target.name);
+        let task = deploy_target(workflow.clone(), index, env_name.to_owned(), output.clone())
+            .instrument(span);
+
+        if workflow.parallel {
+            set.spawn(task);
+        } else {
+            task.await?;
+        }
+    }
+
+    join_all(set).await
 }
 
 trait AlertErr {
-    async fn alert_err(self, trigger: bool) -> Self;
+    async fn alert_err(self, cmd: &Commands) -> Self;
 }
 
 impl<T> AlertErr for Result<T, anyhow::Error> {
-    async fn alert_err(self, trigger: bool) -> Self {
+    async fn alert_err(self, cmd: &Commands) -> Self {
         if let Err(err) = &self {
-            if trigger {
-                Pushover::new()?
+            if cmd.is_run() {
+                Pushover::new(cmd.is_dry_run())?
                     .send(
                         &format!("Workflow执行失败！原因：\r\n{}", err),
                         PushoverSound::FALLING,
@@ -527,46 +1050,91 @@ async fn main() -> Result<(), anyhow::Error> {
     let cmd = Commands::init();
 
     if cmd.is_start() {
-        Pushover::new()?
+        Pushover::new(false)?
             .send("Workflow开始执行！", PushoverSound::BIKE)
             .await
+    } else if let Commands::Cache { action } = &cmd {
+        let config = if Path::new("workflow.toml").is_file() {
+            match WorkflowConfig::read().await?.generator {
+                GeneratorConfig::Hugo(mut hugo) => {
+                    hugo.read_locked_version().await?;
+                    Some(hugo)
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        match action {
+            CacheAction::List => cache_list(config.as_ref()).await,
+            CacheAction::Prune { all } => cache_prune(config.as_ref(), *all).await,
+        }
+    } else if let Commands::HugoInfo = &cmd {
+        let GeneratorConfig::Hugo(mut config) = WorkflowConfig::read().await?.generator else {
+            return Err(anyhow::anyhow!("hugo-info仅支持kind为hugo的[generator]！"));
+        };
+        config.read_locked_version().await?;
+        hugo_info(&config).await
     } else {
-        let config = WorkflowConfig::read().await.alert_err(cmd.is_run()).await?;
-        let hugo = fetch_hugo(config.hugo)
+        let mut config = WorkflowConfig::read().await.alert_err(&cmd).await?;
+        config
+            .generator
+            .resolve_version(cmd.is_update_lock(), cmd.is_dry_run())
             .await
-            .alert_err(cmd.is_run())
+            .alert_err(&cmd)
             .await?;
+        let binary = if cmd.is_dry_run() {
+            tracing::info!("[dry-run] 将获取{}", config.generator.name());
+            PathBuf::from(config.generator.name())
+        } else {
+            config.generator.fetch().await.alert_err(&cmd).await?
+        };
+        let dart_sass = match &config.dart_sass {
+            Some(_) if cmd.is_dry_run() => {
+                tracing::info!("[dry-run] 将获取Dart Sass");
+                None
+            }
+            Some(dart_sass) => Some(fetch_dart_sass(dart_sass).await.alert_err(&cmd).await?),
+            None => None,
+        };
 
         if cmd.is_run() {
             let mp = MemProbe::new();
-            let mut config = config.deploy;
-            config.github.access_token = Some(
-                env_var("DEPLOY_GITHUB_ACCESS_TOKEN")
-                    .alert_err(true)
-                    .await?,
-            );
-            config.github.user_email =
-                Some(env_var("DEPLOY_GITHUB_USER_EMAIL").alert_err(true).await?);
-            config.github.user_name =
-                Some(env_var("DEPLOY_GITHUB_USER_NAME").alert_err(true).await?);
-            config.oss.access_key_id = Some(env_var("OSS_ACCESS_KEY_ID").alert_err(true).await?);
-            config.oss.access_key_secret =
-                Some(env_var("OSS_ACCESS_KEY_SECRET").alert_err(true).await?);
-
-            tracing::info!("================");
-            hugo_deploy(&hugo, &config, true)
-                .await
-                .alert_err(true)
-                .await?;
-
-            tracing::info!("================");
-            hugo_deploy(&hugo, &config, false)
-                .await
-                .alert_err(true)
+            let envs = config
+                .select_environments(cmd.envs())
+                .alert_err(&cmd)
                 .await?;
+            config.validate(&envs).alert_err(&cmd).await?;
+            let envs = envs
+                .into_iter()
+                .map(|(env_name, _)| env_name.to_owned())
+                .collect::<Vec<_>>();
+            let parallel = cmd.is_parallel() || config.deploy.parallel;
+            let workflow = Arc::new(Workflow {
+                config,
+                binary,
+                dart_sass,
+                dry_run: cmd.is_dry_run(),
+                parallel,
+            });
+            let mut set = JoinSet::new();
+
+            for env_name in envs {
+                let span = tracing::info_span!("env", name = %env_name);
+                let task = build_deploy(workflow.clone(), env_name).instrument(span);
+
+                if parallel {
+                    set.spawn(task);
+                } else {
+                    tracing::info!("================");
+                    task.await.alert_err(&cmd).await?;
+                }
+            }
+            join_all(set).await.alert_err(&cmd).await?;
 
             let (mb, _) = mp.join_and_get_mb_sample();
-            Pushover::new()?
+            Pushover::new(cmd.is_dry_run())?
                 .send(
                     &format!("Workflow执行成功！\r\n峰值内存：{} MB", mb),
                     PushoverSound::MAGIC,
@@ -1,89 +1,184 @@
-use opendal::Operator;
+use crate::{retain_decimal_places, OUTPUT_MARKER};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use md5::{Digest, Md5};
+use opendal::{
+    layers::{RetryInterceptor, RetryLayer},
+    Metadata, Metakey, Operator,
+};
 use std::{
+    cell::Cell,
+    collections::HashMap,
+    future::IntoFuture,
     io,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tokio::{
     fs,
-    task::{spawn_blocking, JoinHandle},
+    task::{spawn_blocking, JoinSet},
 };
+use tracing::Instrument;
 use walkdir::WalkDir;
 
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+struct RetryCounter {
+    target: String,
+    times: Cell<usize>,
+}
+
+tokio::task_local! {
+    static RETRY_COUNTER: RetryCounter;
+}
+
+/// Runs `fut` with retries logged against `target`, returning how many retries it took.
+async fn count_retries<T>(target: &str, fut: impl IntoFuture<Output = T>) -> (T, usize) {
+    let counter = RetryCounter {
+        target: target.to_owned(),
+        times: Cell::new(0),
+    };
+    RETRY_COUNTER
+        .scope(counter, async {
+            let res = fut.await;
+            (res, RETRY_COUNTER.with(|c| c.times.get()))
+        })
+        .await
+}
+
+pub struct RetryLogger;
+
+impl RetryInterceptor for RetryLogger {
+    fn intercept(&self, err: &opendal::Error, dur: Duration) {
+        let secs = retain_decimal_places(dur.as_secs_f64(), 3);
+        let counted = RETRY_COUNTER.try_with(|counter| {
+            counter.times.set(counter.times.get() + 1);
+            (counter.target.clone(), counter.times.get())
+        });
+
+        match counted {
+            Ok((target, times)) => {
+                tracing::warn!("{}将在{}秒后第{}次重试，原因：{}", target, secs, times, err)
+            }
+            Err(_) => tracing::warn!("将在{}秒后重试，原因：{}", secs, err),
+        }
+    }
+}
+
+pub fn retry_layer(
+    max_times: Option<usize>,
+    max_delay_secs: Option<u64>,
+) -> RetryLayer<RetryLogger> {
+    let mut layer = RetryLayer::new().with_jitter();
+    if let Some(max_times) = max_times {
+        layer = layer.with_max_times(max_times);
+    }
+    if let Some(max_delay_secs) = max_delay_secs {
+        layer = layer.with_max_delay(Duration::from_secs(max_delay_secs));
+    }
+    layer.with_notify(RetryLogger)
+}
+
 pub struct ConcurrentUploadTasks {
     op: Operator,
-    handles: Vec<JoinHandle<Result<(), opendal::Error>>>,
+    base: PathBuf,
+    concurrency: usize,
+    tasks: usize,
+    set: JoinSet<Result<(), anyhow::Error>>,
 }
 
 impl ConcurrentUploadTasks {
-    pub fn new(op: Operator) -> Self {
+    pub fn new(op: Operator, base: impl AsRef<Path>, concurrency: usize) -> Self {
         Self {
             op,
-            handles: Vec::new(),
+            base: base.as_ref().to_owned(),
+            concurrency: concurrency.max(1),
+            tasks: 0,
+            set: JoinSet::new(),
         }
     }
 
+    async fn join_next(&mut self) -> Result<(), anyhow::Error> {
+        let err = match self.set.join_next().await {
+            Some(Ok(Err(err))) => err,
+            Some(Err(err)) => err.into(),
+            Some(Ok(Ok(()))) | None => return Ok(()),
+        };
+        tracing::error!("上传失败，正在取消剩余上传任务……");
+        self.set.abort_all();
+        Err(err)
+    }
+
     pub async fn push_single_file(
         &mut self,
         src: impl AsRef<Path>,
         target: &str,
-    ) -> Result<(), io::Error> {
-        let data = fs::read(src).await?;
+    ) -> Result<(), anyhow::Error> {
+        while self.set.len() >= self.concurrency {
+            self.join_next().await?;
+        }
+
+        let src = self.base.join(src);
         let op = self.op.clone();
         let target = target.to_owned();
 
-        Ok(self.handles.push(tokio::spawn(async move {
-            tracing::info!("正在上传：{}", target);
-            op.write(&target, data).await
-        })))
+        self.tasks += 1;
+        self.set.spawn(
+            async move {
+                let data = fs::read(src).await?;
+                tracing::info!("正在上传：{}", target);
+
+                let (res, times) = count_retries(&target, op.write(&target, data)).await;
+                res?;
+
+                if times > 0 {
+                    tracing::info!("{}重试{}次后上传成功", target, times);
+                }
+                Ok(())
+            }
+            .in_current_span(),
+        );
+        Ok(())
     }
 
     pub async fn push_path(&mut self, path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
         let path = path.as_ref();
-        Ok(self
-            .push_single_file(
-                path,
-                &path
-                    .to_str()
-                    .ok_or(anyhow::anyhow!("非法路径！"))?
-                    .replace("\\", "/"),
-            )
-            .await?)
-    }
-
-    pub async fn push_str(&mut self, path: &str) -> Result<(), io::Error> {
+        self.push_single_file(path, &path_to_target(path)?).await
+    }
+
+    pub async fn push_str(&mut self, path: &str) -> Result<(), anyhow::Error> {
         self.push_single_file(path, path).await
     }
 
-    pub async fn push_str_seq(&mut self, seq: &Vec<String>) -> Result<(), io::Error> {
+    pub async fn push_str_seq(&mut self, seq: &Vec<String>) -> Result<(), anyhow::Error> {
         for path in seq {
             self.push_str(path).await?;
         }
         Ok(())
     }
 
-    pub async fn join(self) -> Result<usize, anyhow::Error> {
-        let tasks = self.handles.len();
-        let mut results = Vec::new();
-
-        for h in self.handles {
-            results.push(h.await?);
-        }
-
-        for r in results {
-            r?;
+    pub async fn join(mut self) -> Result<usize, anyhow::Error> {
+        while !self.set.is_empty() {
+            self.join_next().await?;
         }
-
-        Ok(tasks)
+        Ok(self.tasks)
     }
 }
 
+pub fn path_to_target(path: impl AsRef<Path>) -> Result<String, anyhow::Error> {
+    Ok(path
+        .as_ref()
+        .to_str()
+        .ok_or(anyhow::anyhow!("非法路径！"))?
+        .replace("\\", "/"))
+}
+
 pub fn collect_files_blocking(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, anyhow::Error> {
     let mut files = Vec::new();
 
     for entry in WalkDir::new(dir) {
         let entry = entry?;
         let path = entry.path();
-        if path.is_file() {
+        if path.is_file() && entry.file_name() != OUTPUT_MARKER {
             files.push(path.to_owned());
         }
     }
@@ -91,24 +186,285 @@ pub fn collect_files_blocking(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, any
     Ok(files)
 }
 
-pub async fn collect_files(dir: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+pub async fn collect_files(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
     let dir = dir.to_owned();
     spawn_blocking(move || collect_files_blocking(dir)).await?
 }
 
-pub async fn sync_dir(op: &Operator, dir: &str) -> Result<usize, anyhow::Error> {
+pub fn file_md5_blocking(path: impl AsRef<Path>) -> Result<String, io::Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Md5::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub async fn file_md5(path: &Path) -> Result<String, anyhow::Error> {
+    let path = path.to_owned();
+    Ok(spawn_blocking(move || file_md5_blocking(path)).await??)
+}
+
+struct RemoteObject {
+    size: u64,
+    md5: Option<String>,
+}
+
+impl RemoteObject {
+    fn from_metadata(meta: &Metadata) -> Self {
+        let md5 = meta
+            .content_md5()
+            .and_then(content_md5_to_hex)
+            .or(meta.etag().and_then(etag_to_md5));
+        RemoteObject {
+            size: meta.content_length(),
+            md5,
+        }
+    }
+}
+
+fn content_md5_to_hex(content_md5: &str) -> Option<String> {
+    match STANDARD.decode(content_md5.trim()) {
+        Ok(md5) if md5.len() == 16 => Some(hex::encode(md5)),
+        _ => None,
+    }
+}
+
+fn etag_to_md5(etag: &str) -> Option<String> {
+    let etag = etag.trim_matches('"');
+    if etag.len() == 32 && etag.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(etag.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+async fn list_remote(
+    op: &Operator,
+    dir: &str,
+) -> Result<HashMap<String, RemoteObject>, anyhow::Error> {
+    let prefix = format!("{}/", dir.trim_end_matches('/'));
+    let list = op
+        .list_with(&prefix)
+        .recursive(true)
+        .metakey(Metakey::Mode | Metakey::ContentLength | Metakey::Etag | Metakey::ContentMd5);
+    let (entries, _) = count_retries(&format!("列出{}", prefix), list).await;
+    let entries = entries?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| entry.metadata().is_file())
+        .map(|entry| {
+            let object = RemoteObject::from_metadata(entry.metadata());
+            (entry.path().to_owned(), object)
+        })
+        .collect())
+}
+
+pub struct SyncPlan {
+    pub uploads: Vec<PathBuf>,
+    pub skipped: usize,
+    pub deletes: Vec<String>,
+}
+
+pub async fn plan_sync_dir(
+    op: &Operator,
+    base: &Path,
+    dir: &str,
+) -> Result<SyncPlan, anyhow::Error> {
     tracing::info!("正在加载目录……");
-    let files = collect_files(dir).await?;
+    let files = collect_files(&base.join(dir)).await?;
+
+    tracing::info!("正在列出旧target……");
+    let remote = list_remote(op, dir).await?;
+
+    tracing::info!("正在比对差异……");
+    diff_files(base, files, remote).await
+}
+
+async fn diff_files(
+    base: &Path,
+    files: Vec<PathBuf>,
+    mut remote: HashMap<String, RemoteObject>,
+) -> Result<SyncPlan, anyhow::Error> {
+    let mut plan = SyncPlan {
+        uploads: Vec::new(),
+        skipped: 0,
+        deletes: Vec::new(),
+    };
 
-    tracing::info!("正在删除旧target……");
-    op.remove_all(dir).await?;
+    for file in files {
+        let path = file.strip_prefix(base)?.to_owned();
+        let unchanged = match remote.remove(&path_to_target(&path)?) {
+            Some(RemoteObject {
+                size,
+                md5: Some(md5),
+            }) if size == fs::metadata(&file).await?.len() => md5 == file_md5(&file).await?,
+            _ => false,
+        };
+
+        if unchanged {
+            plan.skipped += 1;
+        } else {
+            plan.uploads.push(path);
+        }
+    }
+
+    plan.deletes = remote.into_keys().collect();
+    plan.deletes.sort();
+    Ok(plan)
+}
+
+pub async fn sync_dir(
+    op: &Operator,
+    base: &Path,
+    dir: &str,
+    concurrency: usize,
+) -> Result<SyncPlan, anyhow::Error> {
+    let plan = plan_sync_dir(op, base, dir).await?;
 
     tracing::info!("开始上传……");
-    let mut upload = ConcurrentUploadTasks::new(op.clone());
+    let mut upload = ConcurrentUploadTasks::new(op.clone(), base, concurrency);
 
-    for path in files {
+    for path in &plan.uploads {
         upload.push_path(path).await?;
     }
 
-    upload.join().await
+    upload.join().await?;
+
+    if !plan.deletes.is_empty() {
+        tracing::info!("正在删除旧target……");
+        for target in &plan.deletes {
+            tracing::info!("正在删除：{}", target);
+        }
+        let target = format!("删除{}下的{}个旧target", dir, plan.deletes.len());
+        let (res, _) = count_retries(&target, op.remove(plan.deletes.clone())).await;
+        res?;
+    }
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opendal::EntryMode;
+
+    const HELLO_MD5: &str = "5d41402abc4b2a76b9719d911017c592";
+
+    #[test]
+    fn decodes_content_md5() {
+        assert_eq!(
+            content_md5_to_hex("XUFAKrxLKna5cZ2REBfFkg==").as_deref(),
+            Some(HELLO_MD5)
+        );
+        assert_eq!(content_md5_to_hex("aGVsbG8="), None);
+        assert_eq!(content_md5_to_hex("not base64!"), None);
+    }
+
+    #[test]
+    fn parses_plain_etag_only() {
+        assert_eq!(
+            etag_to_md5("\"5D41402ABC4B2A76B9719D911017C592\"").as_deref(),
+            Some(HELLO_MD5)
+        );
+        assert_eq!(etag_to_md5(HELLO_MD5).as_deref(), Some(HELLO_MD5));
+        assert_eq!(etag_to_md5("\"5d41402abc4b2a76b9719d911017c592-2\""), None);
+        assert_eq!(etag_to_md5("W/\"5d41402abc4b2a76b9719d911017c592\""), None);
+    }
+
+    #[test]
+    fn prefers_content_md5_over_etag() {
+        let meta = Metadata::new(EntryMode::FILE)
+            .with_content_length(5)
+            .with_content_md5("XUFAKrxLKna5cZ2REBfFkg==".to_owned())
+            .with_etag("\"00000000000000000000000000000000\"".to_owned());
+        let object = RemoteObject::from_metadata(&meta);
+        assert_eq!(object.size, 5);
+        assert_eq!(object.md5.as_deref(), Some(HELLO_MD5));
+
+        let meta = Metadata::new(EntryMode::FILE)
+            .with_content_length(5)
+            .with_etag("\"5d41402abc4b2a76b9719d911017c592-3\"".to_owned());
+        assert_eq!(RemoteObject::from_metadata(&meta).md5, None);
+    }
+
+    fn remote(size: u64, md5: Option<&str>) -> RemoteObject {
+        RemoteObject {
+            size,
+            md5: md5.map(str::to_owned),
+        }
+    }
+
+    #[tokio::test]
+    async fn plans_uploads_skips_and_deletes() {
+        let base = tempfile::tempdir().unwrap();
+        let dir = base.path().join("public");
+        std::fs::create_dir_all(dir.join("css")).unwrap();
+        for name in ["new.html", "same.html", "changed.html", "multipart.html"] {
+            std::fs::write(dir.join(name), "hello").unwrap();
+        }
+        std::fs::write(dir.join("css/resized.css"), "hello!").unwrap();
+        std::fs::write(dir.join(OUTPUT_MARKER), "").unwrap();
+
+        let remote = HashMap::from([
+            ("public/same.html".to_owned(), remote(5, Some(HELLO_MD5))),
+            (
+                "public/changed.html".to_owned(),
+                remote(5, Some("00000000000000000000000000000000")),
+            ),
+            ("public/multipart.html".to_owned(), remote(5, None)),
+            (
+                "public/css/resized.css".to_owned(),
+                remote(5, Some(HELLO_MD5)),
+            ),
+            ("public/stale.html".to_owned(), remote(3, None)),
+            ("public/old/stale.js".to_owned(), remote(3, None)),
+        ]);
+
+        let files = collect_files(&dir).await.unwrap();
+        let mut plan = diff_files(base.path(), files, remote).await.unwrap();
+        plan.uploads.sort();
+
+        assert_eq!(plan.skipped, 1);
+        assert_eq!(
+            plan.uploads,
+            [
+                "public/changed.html",
+                "public/css/resized.css",
+                "public/multipart.html",
+                "public/new.html",
+            ]
+            .map(PathBuf::from)
+        );
+        assert_eq!(plan.deletes, ["public/old/stale.js", "public/stale.html"]);
+    }
+
+    #[tokio::test]
+    async fn syncs_dir_to_operator() {
+        let base = tempfile::tempdir().unwrap();
+        let dir = base.path().join("public");
+        std::fs::create_dir_all(dir.join("css")).unwrap();
+        std::fs::write(dir.join("index.html"), "hello").unwrap();
+        std::fs::write(dir.join("css/site.css"), "body {}").unwrap();
+
+        let op = Operator::new(opendal::services::Memory::default())
+            .unwrap()
+            .finish();
+        op.write("public/stale.html", "old").await.unwrap();
+        op.write("other/kept.html", "old").await.unwrap();
+
+        let plan = sync_dir(&op, base.path(), "public", 2).await.unwrap();
+        assert_eq!(plan.uploads.len(), 2);
+        assert_eq!(plan.deletes, ["public/stale.html"]);
+
+        assert_eq!(
+            op.read("public/index.html").await.unwrap().to_vec(),
+            b"hello"
+        );
+        assert_eq!(
+            op.read("public/css/site.css").await.unwrap().to_vec(),
+            b"body {}"
+        );
+        assert!(!op.is_exist("public/stale.html").await.unwrap());
+        assert!(op.is_exist("other/kept.html").await.unwrap());
+    }
 }
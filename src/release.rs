@@ -0,0 +1,154 @@
+use crate::download::{
+    cache_key, fetch_release_digest, fetch_with_mirrors, lock_dir, Checksum, Extract,
+};
+use std::{
+    env::consts::{ARCH, EXE_SUFFIX, OS},
+    path::{Path, PathBuf},
+};
+use tokio::process::Command;
+
+pub use crate::download::ReleaseConfig;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReleaseTool {
+    Zola,
+    Mdbook,
+}
+
+impl ReleaseTool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zola => "zola",
+            Self::Mdbook => "mdbook",
+        }
+    }
+
+    fn repo(self) -> &'static str {
+        match self {
+            Self::Zola => "getzola/zola",
+            Self::Mdbook => "rust-lang/mdBook",
+        }
+    }
+
+    fn platform_asset_suffix(self) -> Result<String, anyhow::Error> {
+        let unsupported = || {
+            anyhow::anyhow!(
+                "当前平台（{}-{}）没有可用的{}发布包！请在[generator]中通过asset_suffix指定",
+                OS,
+                ARCH,
+                self.name()
+            )
+        };
+
+        let target = match (OS, ARCH) {
+            ("linux", "x86_64") => "x86_64-unknown-linux-gnu",
+            ("linux", "aarch64") if self == Self::Mdbook => "aarch64-unknown-linux-musl",
+            ("linux", "aarch64") => "aarch64-unknown-linux-gnu",
+            ("macos", "x86_64") => "x86_64-apple-darwin",
+            ("macos", "aarch64") => "aarch64-apple-darwin",
+            ("windows", "x86_64") => return Ok("x86_64-pc-windows-msvc.zip".into()),
+            _ => return Err(unsupported()),
+        };
+
+        Ok(format!("{}.tar.gz", target))
+    }
+}
+
+fn requested_version(config: &ReleaseConfig) -> &str {
+    config.version.trim_start_matches('v')
+}
+
+fn cached_path(tool: ReleaseTool, config: &ReleaseConfig) -> Result<PathBuf, anyhow::Error> {
+    let asset_suffix = config.asset_suffix(|| tool.platform_asset_suffix())?;
+
+    Ok(config
+        .cache_dir(tool.name())?
+        .join(cache_key(requested_version(config), &asset_suffix))
+        .join(format!("{}{}", tool.name(), EXE_SUFFIX)))
+}
+
+async fn check_release(
+    tool: ReleaseTool,
+    config: &ReleaseConfig,
+    binary: &Path,
+) -> Result<bool, anyhow::Error> {
+    tracing::info!("正在校验现有{}版本……", tool.name());
+
+    let Ok(output) = Command::new(binary).arg("--version").output().await else {
+        tracing::info!("{}不存在", tool.name());
+        return Ok(false);
+    };
+    if !output.status.success() {
+        tracing::info!("{} --version执行失败，将重新安装", tool.name());
+        return Ok(false);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
+        .split_whitespace()
+        .last()
+        .unwrap_or_default()
+        .trim_start_matches('v');
+    if version == requested_version(config) {
+        tracing::info!("现有{}版本匹配！", tool.name());
+        Ok(true)
+    } else {
+        tracing::info!("现有{}版本为{}，与要求不匹配", tool.name(), version);
+        Ok(false)
+    }
+}
+
+pub async fn fetch_release(
+    tool: ReleaseTool,
+    config: &ReleaseConfig,
+) -> Result<PathBuf, anyhow::Error> {
+    let version = requested_version(config);
+    tracing::info!("请求的{}版本是：{}", tool.name(), version);
+    let binary = cached_path(tool, config)?;
+    let dir = binary.parent().unwrap_or(Path::new("."));
+    tracing::info!("{}缓存路径：{}", tool.name(), binary.display());
+    let _lock = lock_dir(dir).await?;
+
+    if check_release(tool, config, &binary).await? {
+        tracing::info!("将跳过下载");
+        return Ok(binary);
+    }
+
+    tracing::info!("准备下载{}", tool.name());
+    let asset = format!(
+        "{}-v{}-{}",
+        tool.name(),
+        version,
+        config.asset_suffix(|| tool.platform_asset_suffix())?
+    );
+    let client = config.client()?;
+    let expected = match config.sha256() {
+        Some(sha256) => sha256,
+        None => {
+            let default_releases_url =
+                format!("https://api.github.com/repos/{}/releases", tool.repo());
+            let url = format!(
+                "{}/tags/v{}",
+                config.releases_url(&default_releases_url),
+                version
+            );
+            fetch_release_digest(&client, &url, &asset)
+                .await
+                .map_err(|err| anyhow::anyhow!("{}请在[generator]中通过sha256指定", err))?
+        }
+    };
+    let default_mirror = format!(
+        "https://github.com/{}/releases/download/v{{version}}",
+        tool.repo()
+    );
+
+    fetch_with_mirrors(
+        &client,
+        &config.mirrors(&default_mirror, version),
+        &asset,
+        &Checksum::Sha256(expected),
+        dir,
+        Extract::Binary(tool.name()),
+    )
+    .await
+}